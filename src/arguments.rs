@@ -1,30 +1,46 @@
-use crate::{config::CONFIG, errors::DotfilesError, regex::HEX_COLOUR_REGEX};
+use crate::{
+    colour::Colour,
+    config::{FileConfig, config},
+    diagnostics::closest_match,
+    errors::DotfilesError,
+    expression::Value,
+    theme::theme_name,
+};
 
-pub fn parse_argument(arg: &str) -> Result<String, DotfilesError> {
-    // Get the current theme
-    let themes = CONFIG.get_theme_hashmap();
-    let Some(current_theme) = themes.get(&CONFIG.theme) else {
+pub fn parse_argument(arg: &str, file_config: &FileConfig) -> Result<Value, DotfilesError> {
+    // Get the current theme, using the file's own theme (With its variables) if it has one
+    let config = config()?;
+    let themes = config.get_theme_hashmap();
+    let Some(current_theme) = file_config
+        .file_theme
+        .as_ref()
+        .or_else(|| themes.get(&config.theme))
+    else {
         return Err(DotfilesError::ThemeNotFound {
-            name: CONFIG.theme.clone(),
-            themes: CONFIG
-                .themes
-                .iter()
-                .map(|theme| theme["name"].clone())
-                .collect(),
+            name: config.theme.clone(),
+            themes: config.get_theme_names(),
         });
     };
+    let theme_name = theme_name(current_theme);
 
     // See if the agument is a variable within this theme, if it isn't then check if it is a HEX colour code
     let Some(value) = current_theme.get(arg) else {
-        if (*HEX_COLOUR_REGEX).clone()?.is_match(arg) {
-            return Ok(arg.to_string());
+        if Colour::from_hex(arg).is_ok() {
+            return Ok(Value::Colour(arg.to_string()));
         }
 
         return Err(DotfilesError::ArgNotFound {
             arg: arg.to_string(),
-            theme_hashmap: current_theme.clone(),
+            theme: theme_name,
+            suggestion: closest_match(
+                arg,
+                current_theme
+                    .keys()
+                    .map(String::as_str)
+                    .filter(|key| *key != "name"),
+            ),
         });
     };
 
-    Ok(value.to_string())
+    Ok(value.clone())
 }
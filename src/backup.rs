@@ -0,0 +1,312 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cli::info;
+use crate::config::config;
+use crate::errors::DotfilesError;
+use crate::file::{open_file, write_to_file};
+
+const BACKUPS_SUB_PATH: &str = "dotfile-templater/backups";
+const MANIFEST_FILE_NAME: &str = "manifest.toml";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Manifest {
+    pub created: String,
+    pub theme: String,
+    #[serde(default)]
+    pub files: Vec<BackupEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BackupEntry {
+    // The real path of the file which was modified (After following symlinks)
+    pub path: String,
+    // The name of the copy of the original file, within the snapshot folder
+    pub backup: String,
+    // Hash of the contents which were written, to check the file hasn't been changed since
+    pub written_hash: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub dir: PathBuf,
+    pub manifest: Manifest,
+}
+
+impl Snapshot {
+    pub fn create() -> Result<Self, DotfilesError> {
+        let backups_dir = get_backups_dir()?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        // Name the folder by the time, so the folders are sorted from oldest to newest (Adding a number if there is already a backup from this second)
+        let timestamp = format_timestamp(now);
+        let mut dir = backups_dir.join(&timestamp);
+        let mut suffix = 1;
+        while dir.exists() {
+            dir = backups_dir.join(format!("{timestamp}-{suffix}"));
+            suffix += 1;
+        }
+        fs::create_dir_all(&dir)?;
+
+        let snapshot = Self {
+            dir,
+            manifest: Manifest {
+                created: timestamp,
+                theme: config()?.theme.clone(),
+                files: Vec::new(),
+            },
+        };
+        snapshot.write_manifest()?;
+
+        Ok(snapshot)
+    }
+
+    pub fn load(dir: PathBuf) -> Result<Self, DotfilesError> {
+        let manifest = toml::from_str(&open_file(dir.join(MANIFEST_FILE_NAME).to_string_lossy())?)?;
+
+        Ok(Self { dir, manifest })
+    }
+
+    pub fn add_file(
+        &mut self,
+        path: &str,
+        old_contents: &str,
+        new_contents: &str,
+    ) -> Result<(), DotfilesError> {
+        // Save a copy of the file before it is modified
+        let backup = self.manifest.files.len().to_string();
+        fs::write(self.dir.join(&backup), old_contents)?;
+
+        // Keep the manifest up to date on disk, so files modified before a failure can still be restored
+        self.manifest.files.push(BackupEntry {
+            path: fs::canonicalize(path)?.display().to_string(),
+            backup,
+            written_hash: hash_contents(new_contents),
+        });
+        self.write_manifest()
+    }
+
+    // Forget every file except these, so undo only restores the files which are still modified
+    pub fn retain_files(&mut self, paths: &[String]) -> Result<(), DotfilesError> {
+        let real_paths = paths
+            .iter()
+            .filter_map(|path| fs::canonicalize(path).ok())
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>();
+        self.manifest
+            .files
+            .retain(|entry| real_paths.contains(&entry.path));
+        self.write_manifest()
+    }
+
+    pub fn name(&self) -> String {
+        self.dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    fn write_manifest(&self) -> Result<(), DotfilesError> {
+        fs::write(
+            self.dir.join(MANIFEST_FILE_NAME),
+            toml::to_string(&self.manifest)?,
+        )?;
+
+        Ok(())
+    }
+}
+
+pub fn get_backups_dir() -> Result<PathBuf, DotfilesError> {
+    // Use $XDG_STATE_HOME, falling back to its default of ~/.local/state
+    let state_dir = match std::env::var("XDG_STATE_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => match std::env::var("HOME") {
+            Ok(home) if !home.is_empty() => Path::new(&home).join(".local/state"),
+            _ => return Err(DotfilesError::EnvVarNotFound("XDG_STATE_HOME".to_string())),
+        },
+    };
+
+    Ok(state_dir.join(BACKUPS_SUB_PATH))
+}
+
+pub fn list_snapshots() -> Result<Vec<Snapshot>, DotfilesError> {
+    let backups_dir = get_backups_dir()?;
+    if !backups_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    // Every folder with a manifest is a snapshot, sorted from oldest to newest
+    let mut dirs = fs::read_dir(backups_dir)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|dir| dir.join(MANIFEST_FILE_NAME).is_file())
+        .collect::<Vec<_>>();
+    dirs.sort_by_key(|dir| snapshot_sort_key(dir));
+
+    dirs.into_iter().map(Snapshot::load).collect()
+}
+
+pub fn undo_last_snapshot() -> Result<(), DotfilesError> {
+    let Some(snapshot) = list_snapshots()?.pop() else {
+        return Err(DotfilesError::BackupNotFound);
+    };
+
+    // Check every file before restoring any of them, so nothing is restored if a file has been changed since
+    let mut restores = Vec::new();
+    for entry in &snapshot.manifest.files {
+        let current_contents = open_file(&entry.path)?;
+        if hash_contents(&current_contents) != entry.written_hash {
+            return Err(DotfilesError::BackupFileChanged {
+                path: entry.path.clone(),
+                snapshot: snapshot.name(),
+            });
+        }
+
+        restores.push((
+            entry.path.as_str(),
+            open_file(snapshot.dir.join(&entry.backup).to_string_lossy())?,
+            current_contents,
+        ));
+    }
+
+    for (i, (path, contents, _)) in restores.iter().enumerate() {
+        // Put back the files which were already restored, so they still match the backup and it can be undone again
+        if let Err(e) = write_to_file(path, contents) {
+            let not_restored = restores[..i]
+                .iter()
+                .filter(|(path, _, current_contents)| {
+                    write_to_file(path, current_contents).is_err()
+                })
+                .map(|(path, _, _)| (*path).to_string())
+                .collect();
+
+            return Err(DotfilesError::UndoRolledBack {
+                path: (*path).to_string(),
+                error: Box::new(e),
+                not_restored,
+            });
+        }
+    }
+    for (path, _, _) in &restores {
+        info!("Restored {path}");
+    }
+
+    // The snapshot has been used, so the next undo goes to the one before it
+    fs::remove_dir_all(&snapshot.dir)?;
+    info!(
+        "Undid the changes from {} (Theme '{}')",
+        snapshot.manifest.created, snapshot.manifest.theme
+    );
+
+    Ok(())
+}
+
+pub fn prune_snapshots() -> Result<usize, DotfilesError> {
+    let snapshots = list_snapshots()?;
+
+    // Remove the oldest snapshots, keeping the number set in the config
+    let remove_count = snapshots.len().saturating_sub(config()?.backup_count);
+    for snapshot in &snapshots[..remove_count] {
+        fs::remove_dir_all(&snapshot.dir)?;
+    }
+
+    Ok(remove_count)
+}
+
+fn snapshot_sort_key(dir: &Path) -> (String, usize) {
+    // Sort by the timestamp, then by the number added to snapshots made in the same second
+    let name = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    match name.rsplit_once('Z') {
+        Some((timestamp, suffix)) => (
+            timestamp.to_string(),
+            suffix.trim_start_matches('-').parse().unwrap_or(0),
+        ),
+        None => (name, 0),
+    }
+}
+
+fn hash_contents(contents: &str) -> String {
+    // FNV-1a, since it is stable between builds (Unlike the standard library's hasher)
+    let hash = contents
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+
+    format!("{hash:016x}")
+}
+
+fn format_timestamp(unix_secs: u64) -> String {
+    // Convert the days since 1970 into a (UTC) date (From Howard Hinnant's civil_from_days)
+    let days = unix_secs / 86_400;
+    let secs_of_day = unix_secs % 86_400;
+
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}-{:02}-{:02}Z",
+        secs_of_day / 3600,
+        (secs_of_day % 3600) / 60,
+        secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00-00-00Z");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29T00-00-00Z");
+        assert_eq!(format_timestamp(1_709_251_199), "2024-02-29T23-59-59Z");
+        assert_eq!(format_timestamp(1_735_689_600), "2025-01-01T00-00-00Z");
+    }
+
+    #[test]
+    fn snapshots_sort_by_time_then_suffix() {
+        let mut dirs = [
+            "2025-01-01T00-00-00Z-10",
+            "2025-01-01T00-00-01Z",
+            "2025-01-01T00-00-00Z-2",
+            "2024-12-31T23-59-59Z",
+            "2025-01-01T00-00-00Z",
+        ]
+        .map(|name| Path::new("/backups").join(name));
+        dirs.sort_by_key(|dir| snapshot_sort_key(dir));
+
+        assert_eq!(
+            dirs.map(|dir| dir.display().to_string().replace("/backups/", "")),
+            [
+                "2024-12-31T23-59-59Z",
+                "2025-01-01T00-00-00Z",
+                "2025-01-01T00-00-00Z-2",
+                "2025-01-01T00-00-00Z-10",
+                "2025-01-01T00-00-01Z",
+            ]
+        );
+    }
+}
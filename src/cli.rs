@@ -0,0 +1,209 @@
+use std::sync::OnceLock;
+
+use crate::errors::DotfilesError;
+
+const HELP_TEXT: &str = "\
+Usage: dotfile_templater [OPTIONS] [COMMAND]
+
+Commands:
+  apply               Modify the files according to their templates (Default)
+  check               Check every theme, then report which files would be modified, without modifying them
+  diff                Show a diff of the changes which would be made, without modifying any files
+  list-themes         List the names of every theme
+  show-theme <NAME>   Show every variable of a theme
+  switch <THEME>      Set the theme in the config file, then apply it
+  import <FILE>       Save a base16/base24 scheme as a theme file
+  undo                Restore the files changed by the last apply, from their backup
+  backups list        List the backups, from oldest to newest
+  backups prune       Remove the oldest backups, keeping the number set by backup_count
+
+Options:
+  -c, --config <PATH>  Use this config file instead of $XDG_CONFIG_HOME/dotfile-templater/config.toml
+  -t, --theme <NAME>   Use this theme for this run, instead of the one in the config
+  -f, --file <PATH>    Only run on this file from the config (Can be given multiple times)
+  -n, --dry-run        Show a diff instead of modifying any files (Same as diff, when applying)
+  -s, --strict         Don't modify any files if any template has an error
+  -q, --quiet          Only print errors
+  -v, --verbose        Also print each function as it is run
+  -h, --help           Print this help text
+  -V, --version        Print the version";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Verbosity {
+    Quiet,
+    #[default]
+    Normal,
+    Verbose,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Command {
+    #[default]
+    Apply,
+    Check,
+    Diff,
+    ListThemes,
+    ShowTheme(String),
+    Switch(String),
+    Import(String),
+    Undo,
+    ListBackups,
+    PruneBackups,
+    Help,
+    Version,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Cli {
+    pub command: Command,
+    pub config_path: Option<String>,
+    pub theme: Option<String>,
+    pub files: Vec<String>,
+    pub dry_run: bool,
+    pub strict: bool,
+    pub verbosity: Verbosity,
+}
+
+pub static CLI: OnceLock<Cli> = OnceLock::new();
+
+pub fn cli() -> &'static Cli {
+    CLI.get_or_init(Cli::default)
+}
+
+pub fn verbosity() -> Verbosity {
+    cli().verbosity
+}
+
+// Whether a diff is printed, in which case only the diff goes to stdout (So it can be piped to patch)
+pub fn prints_diff() -> bool {
+    match cli().command {
+        Command::Diff => true,
+        Command::Apply | Command::Switch(_) => cli().dry_run,
+        _ => false,
+    }
+}
+
+// Print a line unless --quiet was given (To stderr when printing a diff)
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::cli::verbosity() >= $crate::cli::Verbosity::Normal {
+            if $crate::cli::prints_diff() {
+                eprintln!($($arg)*);
+            } else {
+                println!($($arg)*);
+            }
+        }
+    };
+}
+
+// Print a line only if --verbose was given (To stderr when printing a diff)
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::cli::verbosity() >= $crate::cli::Verbosity::Verbose {
+            if $crate::cli::prints_diff() {
+                eprintln!($($arg)*);
+            } else {
+                println!($($arg)*);
+            }
+        }
+    };
+}
+
+pub(crate) use info;
+pub(crate) use verbose;
+
+pub fn print_help() {
+    println!("{HELP_TEXT}");
+}
+
+pub fn parse_cli<I: IntoIterator<Item = String>>(args: I) -> Result<Cli, DotfilesError> {
+    let mut cli = Cli::default();
+    let mut command_words = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        // Allow options to be given as "--option=value" as well as "--option value"
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => {
+                (flag.to_string(), Some(value.to_string()))
+            }
+            _ => (arg.clone(), None),
+        };
+
+        // Get the value for an option which needs one
+        let mut option_value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| DotfilesError::CliError(format!("Option '{flag}' needs a value")))
+        };
+
+        match flag.as_str() {
+            "-c" | "--config" => cli.config_path = Some(option_value()?),
+            "-t" | "--theme" => cli.theme = Some(option_value()?),
+            "-f" | "--file" => cli.files.push(option_value()?),
+            "-n" | "--dry-run" => cli.dry_run = true,
+            "-s" | "--strict" => cli.strict = true,
+            "-q" | "--quiet" => cli.verbosity = Verbosity::Quiet,
+            "-v" | "--verbose" => cli.verbosity = Verbosity::Verbose,
+            "-h" | "--help" => command_words = vec!["help".to_string()],
+            "-V" | "--version" => command_words = vec!["version".to_string()],
+            f if f.starts_with('-') && f.len() > 1 => {
+                return Err(DotfilesError::CliError(format!("Unknown option '{f}'")));
+            }
+            _ => command_words.push(arg),
+        }
+    }
+
+    cli.command = parse_command(&command_words)?;
+
+    Ok(cli)
+}
+
+fn parse_command(words: &[String]) -> Result<Command, DotfilesError> {
+    let Some((name, args)) = words.split_first() else {
+        return Ok(Command::Apply);
+    };
+
+    // Commands which take a single name as their argument
+    let single_arg = |command: fn(String) -> Command| match args {
+        [arg] => Ok(command(arg.clone())),
+        _ => Err(DotfilesError::CliError(format!(
+            "Command '{name}' needs 1 argument, found {}: {args:?}",
+            args.len()
+        ))),
+    };
+
+    let command = match name.as_str() {
+        "show-theme" => return single_arg(Command::ShowTheme),
+        "switch" => return single_arg(Command::Switch),
+        "import" => return single_arg(Command::Import),
+        "backups" => {
+            return match args.first().map(String::as_str) {
+                Some("list") if args.len() == 1 => Ok(Command::ListBackups),
+                Some("prune") if args.len() == 1 => Ok(Command::PruneBackups),
+                _ => Err(DotfilesError::CliError(format!(
+                    "Command 'backups' needs 'list' or 'prune', found: {args:?}"
+                ))),
+            };
+        }
+        "undo" => Command::Undo,
+        "apply" => Command::Apply,
+        "check" => Command::Check,
+        "diff" => Command::Diff,
+        "list-themes" => Command::ListThemes,
+        "help" => Command::Help,
+        "version" => Command::Version,
+        c => return Err(DotfilesError::CliError(format!("Unknown command '{c}'"))),
+    };
+
+    // The remaining commands take no arguments
+    if args.is_empty() {
+        Ok(command)
+    } else {
+        Err(DotfilesError::CliError(format!(
+            "Command '{name}' takes no arguments, found {}: {args:?}",
+            args.len()
+        )))
+    }
+}
@@ -0,0 +1,759 @@
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::diagnostics::closest_match;
+use crate::errors::DotfilesError;
+
+// Matches a whole hex colour, with 3, 4, 6 or 8 digits (So the alpha of #RRGGBBAA isn't left behind)
+pub const COLOUR_PATTERN: &str = r"#(?:[0-9A-Fa-f]{8}|[0-9A-Fa-f]{6}|[0-9A-Fa-f]{3,4})\b";
+
+// The largest chroma of an sRGB colour in OKLCH is about 0.37, so saturation amounts are scaled by this
+const OKLCH_MAX_CHROMA: f64 = 0.4;
+
+// Below this, a colour is grey, so its hue doesn't mean anything
+const ACHROMATIC_THRESHOLD: f64 = 1e-4;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Colour {
+    // Each channel is between 0 and 1
+    pub red: f64,
+    pub green: f64,
+    pub blue: f64,
+    pub alpha: f64,
+}
+
+// Colours are equal if they are written as the same hex (So #fff, #FFFFFF and #ffffffff are all equal)
+impl PartialEq for Colour {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_hex() == other.to_hex()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColourSpace {
+    #[default]
+    Hsl,
+    Oklch,
+}
+
+impl FromStr for ColourSpace {
+    type Err = DotfilesError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "hsl" => Ok(Self::Hsl),
+            "oklch" => Ok(Self::Oklch),
+            _ => Err(DotfilesError::UnknownColourSpace(s.to_string())),
+        }
+    }
+}
+
+// How the replace-col functions write the new colour
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum HexStyle {
+    // Exactly as the value is written
+    #[default]
+    Exact,
+    // In the same case and length (Such as #fff or #FFFFFF) as the colour being replaced
+    Preserve,
+}
+
+impl FromStr for HexStyle {
+    type Err = DotfilesError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "exact" => Ok(Self::Exact),
+            "preserve" => Ok(Self::Preserve),
+            _ => Err(DotfilesError::UnknownHexStyle(s.to_string())),
+        }
+    }
+}
+
+// Write a hex colour in the same case and length as another, where possible (#FFF can only be shortened if each pair of digits is the same)
+pub fn match_hex_style(hex: &str, style_of: &str) -> String {
+    let digits = hex.trim_start_matches('#');
+    let style_digits = style_of.trim_start_matches('#');
+
+    let pairs = digits.as_bytes().chunks(2).collect::<Vec<_>>();
+    let digits = match (digits.len(), style_digits.len()) {
+        // Shorten, if every pair of digits is a repeated digit
+        (6 | 8, 3 | 4)
+            if pairs
+                .iter()
+                .all(|pair| pair[0].eq_ignore_ascii_case(&pair[1])) =>
+        {
+            pairs.iter().map(|pair| char::from(pair[0])).collect()
+        }
+        // Lengthen, by repeating each digit
+        (3 | 4, 6 | 8) => digits.chars().flat_map(|c| [c, c]).collect(),
+        _ => digits.to_string(),
+    };
+
+    // Only change the case if the old colour's letters were all one case
+    let has_lowercase = style_digits.chars().any(|c| c.is_ascii_lowercase());
+    let has_uppercase = style_digits.chars().any(|c| c.is_ascii_uppercase());
+    let digits = match (has_lowercase, has_uppercase) {
+        (true, false) => digits.to_lowercase(),
+        (false, true) => digits.to_uppercase(),
+        _ => digits,
+    };
+
+    format!("#{digits}")
+}
+
+// The notations which a colour can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColourFormat {
+    // #RRGGBB (Or #RRGGBBAA if the colour is see-through)
+    Hex,
+    // RRGGBB
+    BareHex,
+    // 0xRRGGBB
+    ZeroX,
+    // rgb(r, g, b)
+    Rgb,
+    // rgba(r, g, b, a), where the alpha is between 0 and 1
+    Rgba,
+    // r;g;b, as used by ANSI escape codes
+    Ansi,
+    // 0.58, 0.29, 1.0 (With the alpha as a fourth number if the colour is see-through)
+    Float,
+    // #AARRGGBB
+    Argb,
+}
+
+// The name of each format, as it is written in a template
+pub const COLOUR_FORMAT_NAMES: [(&str, ColourFormat); 8] = [
+    ("hex", ColourFormat::Hex),
+    ("hex-bare", ColourFormat::BareHex),
+    ("0x", ColourFormat::ZeroX),
+    ("rgb", ColourFormat::Rgb),
+    ("rgba", ColourFormat::Rgba),
+    ("ansi", ColourFormat::Ansi),
+    ("float", ColourFormat::Float),
+    ("argb", ColourFormat::Argb),
+];
+
+impl FromStr for ColourFormat {
+    type Err = DotfilesError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let names = COLOUR_FORMAT_NAMES.map(|(name, _)| name);
+
+        COLOUR_FORMAT_NAMES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+            .map(|(_, format)| *format)
+            .ok_or_else(|| DotfilesError::UnknownColourFormat {
+                name: s.to_string(),
+                formats: names.iter().map(ToString::to_string).collect(),
+                suggestion: closest_match(s, names),
+            })
+    }
+}
+
+impl ColourFormat {
+    pub fn render(self, colour: Colour) -> String {
+        let [red, green, blue, alpha] =
+            [colour.red, colour.green, colour.blue, colour.alpha].map(channel_to_byte);
+
+        match self {
+            Self::Hex => colour.to_hex(),
+            Self::BareHex => format!("{red:02X}{green:02X}{blue:02X}"),
+            Self::ZeroX => format!("0x{red:02X}{green:02X}{blue:02X}"),
+            Self::Rgb => format!("rgb({red}, {green}, {blue})"),
+            Self::Rgba => format!(
+                "rgba({red}, {green}, {blue}, {})",
+                format_float(colour.alpha)
+            ),
+            Self::Ansi => format!("{red};{green};{blue}"),
+            Self::Float => {
+                let mut channels = vec![colour.red, colour.green, colour.blue];
+                if !colour.is_opaque() {
+                    channels.push(colour.alpha);
+                }
+
+                channels
+                    .into_iter()
+                    .map(format_float)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }
+            Self::Argb => format!("#{alpha:02X}{red:02X}{green:02X}{blue:02X}"),
+        }
+    }
+
+    // Write the colour in this format to replace old_text, keeping the old colour's alpha if the new colour doesn't have its own (Like replace-col does for hex)
+    pub fn render_replacing(self, colour: Colour, old_text: Option<&str>) -> String {
+        match old_text.and_then(|old_text| self.alpha(old_text)) {
+            Some(alpha) if colour.is_opaque() => self.render(colour.with_alpha(alpha)),
+            _ => self.render(colour),
+        }
+    }
+
+    // The alpha of a colour written in this format, for the formats which can have one (Such as the 0.5 of rgba(1, 2, 3, 0.5))
+    fn alpha(self, text: &str) -> Option<f64> {
+        match self {
+            Self::Hex => Colour::from_hex(text).ok().map(|colour| colour.alpha),
+            Self::Rgba | Self::Float => {
+                let numbers = text
+                    .trim_start_matches("rgba(")
+                    .trim_end_matches(')')
+                    .split(',')
+                    .collect::<Vec<_>>();
+
+                match numbers[..] {
+                    [_, _, _, alpha] => alpha.trim().parse().ok(),
+                    _ => None,
+                }
+            }
+            Self::Argb => u8::from_str_radix(text.get(1..3)?, 16)
+                .ok()
+                .map(|alpha| f64::from(alpha) / 255.0),
+            Self::BareHex | Self::ZeroX | Self::Rgb | Self::Ansi => None,
+        }
+    }
+
+    // A regex which matches a colour written in this format
+    pub const fn pattern(self) -> &'static str {
+        match self {
+            Self::Hex => COLOUR_PATTERN,
+            Self::BareHex => r"\b[0-9A-Fa-f]{6}\b",
+            Self::ZeroX => r"\b0[xX][0-9A-Fa-f]{6}\b",
+            Self::Rgb => r"rgb\(\s*\d{1,3}\s*,\s*\d{1,3}\s*,\s*\d{1,3}\s*\)",
+            Self::Rgba => r"rgba\(\s*\d{1,3}\s*,\s*\d{1,3}\s*,\s*\d{1,3}\s*,\s*(?:\d*\.)?\d+\s*\)",
+            Self::Ansi => r"\b\d{1,3};\d{1,3};\d{1,3}\b",
+            Self::Float => r"(?:\d*\.)?\d+(?:\s*,\s*(?:\d*\.)?\d+){2,3}",
+            Self::Argb => r"#[0-9A-Fa-f]{8}\b",
+        }
+    }
+}
+
+// A colour in a cylindrical space: HSL (Hue, saturation, lightness) or OKLCH (Hue, chroma, lightness)
+#[derive(Debug, Clone, Copy)]
+struct Polar {
+    hue: f64,
+    saturation: f64,
+    lightness: f64,
+}
+
+impl Colour {
+    pub fn from_hex(text: &str) -> Result<Self, DotfilesError> {
+        let invalid = || DotfilesError::InvalidColour(text.to_string());
+
+        let hex = text.strip_prefix('#').ok_or_else(invalid)?;
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+
+        // Accept #RGB, #RGBA, #RRGGBB and #RRGGBBAA (Short forms have each digit doubled, so #fff is #ffffff)
+        let digits_per_channel = match hex.len() {
+            3 | 4 => 1,
+            6 | 8 => 2,
+            _ => return Err(invalid()),
+        };
+
+        let channel = |i: usize| {
+            hex.get(i * digits_per_channel..(i + 1) * digits_per_channel)
+                .and_then(|digits| {
+                    u8::from_str_radix(&digits.repeat(3 - digits_per_channel), 16).ok()
+                })
+                .map(|value| f64::from(value) / 255.0)
+        };
+
+        Ok(Self {
+            red: channel(0).ok_or_else(invalid)?,
+            green: channel(1).ok_or_else(invalid)?,
+            blue: channel(2).ok_or_else(invalid)?,
+            alpha: channel(3).unwrap_or(1.0),
+        })
+    }
+
+    pub fn is_opaque(self) -> bool {
+        channel_to_byte(self.alpha) == u8::MAX
+    }
+
+    // The alpha channel as two hex digits
+    pub fn alpha_hex(self) -> String {
+        format!("{:02X}", channel_to_byte(self.alpha))
+    }
+
+    pub fn to_hex(self) -> String {
+        let [red, green, blue, alpha] =
+            [self.red, self.green, self.blue, self.alpha].map(channel_to_byte);
+
+        // Only write the alpha channel if the colour is see-through
+        if self.is_opaque() {
+            format!("#{red:02X}{green:02X}{blue:02X}")
+        } else {
+            format!("#{red:02X}{green:02X}{blue:02X}{alpha:02X}")
+        }
+    }
+
+    pub fn lighten(self, amount: f64, space: ColourSpace) -> Self {
+        self.map_polar(space, |polar| Polar {
+            lightness: (polar.lightness + amount).clamp(0.0, 1.0),
+            ..polar
+        })
+    }
+
+    pub fn saturate(self, amount: f64, space: ColourSpace) -> Self {
+        let (amount, max) = match space {
+            ColourSpace::Hsl => (amount, 1.0),
+            ColourSpace::Oklch => (amount * OKLCH_MAX_CHROMA, f64::INFINITY),
+        };
+
+        self.map_polar(space, |polar| Polar {
+            saturation: (polar.saturation + amount).clamp(0.0, max),
+            ..polar
+        })
+    }
+
+    pub fn complement(self, space: ColourSpace) -> Self {
+        self.map_polar(space, |polar| Polar {
+            hue: (polar.hue + 180.0).rem_euclid(360.0),
+            ..polar
+        })
+    }
+
+    pub fn invert(self) -> Self {
+        Self {
+            red: 1.0 - self.red,
+            green: 1.0 - self.green,
+            blue: 1.0 - self.blue,
+            alpha: self.alpha,
+        }
+    }
+
+    pub const fn with_alpha(self, alpha: f64) -> Self {
+        Self {
+            alpha: alpha.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+
+    // Mix with another colour, where a ratio of 0 is this colour and 1 is the other colour
+    pub fn mix(self, other: Self, ratio: f64, space: ColourSpace) -> Self {
+        let ratio = ratio.clamp(0.0, 1.0);
+        let lerp = |a: f64, b: f64| (b - a).mul_add(ratio, a);
+
+        let (mut from, mut to) = (self.to_polar(space), other.to_polar(space));
+
+        // A grey has no hue, so use the other colour's hue (Otherwise mixing with grey would shift the hue towards red)
+        match (from.is_achromatic(space), to.is_achromatic(space)) {
+            (true, false) => from.hue = to.hue,
+            (false, true) => to.hue = from.hue,
+            _ => {}
+        }
+
+        // Go the shortest way around the hue circle
+        let mut hue_difference = to.hue - from.hue;
+        if hue_difference > 180.0 {
+            hue_difference -= 360.0;
+        } else if hue_difference < -180.0 {
+            hue_difference += 360.0;
+        }
+
+        let polar = Polar {
+            hue: hue_difference.mul_add(ratio, from.hue).rem_euclid(360.0),
+            saturation: lerp(from.saturation, to.saturation),
+            lightness: lerp(from.lightness, to.lightness),
+        };
+
+        Self::from_polar(polar, space, lerp(self.alpha, other.alpha))
+    }
+
+    fn map_polar<F: Fn(Polar) -> Polar>(self, space: ColourSpace, f: F) -> Self {
+        Self::from_polar(f(self.to_polar(space)), space, self.alpha)
+    }
+
+    fn to_polar(self, space: ColourSpace) -> Polar {
+        match space {
+            ColourSpace::Hsl => self.to_hsl(),
+            ColourSpace::Oklch => self.to_oklch(),
+        }
+    }
+
+    fn from_polar(polar: Polar, space: ColourSpace, alpha: f64) -> Self {
+        let [red, green, blue] = match space {
+            ColourSpace::Hsl => hsl_to_rgb(polar),
+            ColourSpace::Oklch => oklch_to_rgb(polar),
+        };
+
+        // Colours outside of sRGB are clipped to the nearest channel values
+        Self {
+            red: red.clamp(0.0, 1.0),
+            green: green.clamp(0.0, 1.0),
+            blue: blue.clamp(0.0, 1.0),
+            alpha,
+        }
+    }
+
+    fn to_hsl(self) -> Polar {
+        let max = self.red.max(self.green).max(self.blue);
+        let min = self.red.min(self.green).min(self.blue);
+        let lightness = f64::midpoint(max, min);
+        let delta = max - min;
+
+        if delta < ACHROMATIC_THRESHOLD {
+            return Polar {
+                hue: 0.0,
+                saturation: 0.0,
+                lightness,
+            };
+        }
+
+        let hue = if (max - self.red).abs() < f64::EPSILON {
+            60.0 * ((self.green - self.blue) / delta)
+        } else if (max - self.green).abs() < f64::EPSILON {
+            60.0 * ((self.blue - self.red) / delta + 2.0)
+        } else {
+            60.0 * ((self.red - self.green) / delta + 4.0)
+        };
+
+        Polar {
+            hue: hue.rem_euclid(360.0),
+            saturation: delta / (1.0 - 2.0f64.mul_add(lightness, -1.0).abs()),
+            lightness,
+        }
+    }
+
+    fn to_oklch(self) -> Polar {
+        let [l, a, b] = linear_srgb_to_oklab([self.red, self.green, self.blue].map(srgb_to_linear));
+
+        Polar {
+            hue: b.atan2(a).to_degrees().rem_euclid(360.0),
+            saturation: a.hypot(b),
+            lightness: l,
+        }
+    }
+}
+
+impl Polar {
+    fn is_achromatic(self, space: ColourSpace) -> bool {
+        match space {
+            ColourSpace::Hsl => self.saturation < ACHROMATIC_THRESHOLD,
+            ColourSpace::Oklch => self.saturation < ACHROMATIC_THRESHOLD * OKLCH_MAX_CHROMA,
+        }
+    }
+}
+
+fn format_float(channel: f64) -> String {
+    // Round to 3 decimal places, without trailing zeros (But keeping one after the point, so 1 is 1.0)
+    let text = format!("{channel:.3}");
+    let text = text.trim_end_matches('0');
+
+    if text.ends_with('.') {
+        format!("{text}0")
+    } else {
+        text.to_string()
+    }
+}
+
+fn channel_to_byte(channel: f64) -> u8 {
+    // The channel is clamped first, so this can't overflow
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let byte = (channel.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+    byte
+}
+
+fn hsl_to_rgb(polar: Polar) -> [f64; 3] {
+    let chroma = (1.0 - 2.0f64.mul_add(polar.lightness, -1.0).abs()) * polar.saturation;
+    let hue_sector = polar.hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (hue_sector % 2.0 - 1.0).abs());
+    let m = polar.lightness - chroma / 2.0;
+
+    let [red, green, blue] = match hue_sector {
+        h if h < 1.0 => [chroma, x, 0.0],
+        h if h < 2.0 => [x, chroma, 0.0],
+        h if h < 3.0 => [0.0, chroma, x],
+        h if h < 4.0 => [0.0, x, chroma],
+        h if h < 5.0 => [x, 0.0, chroma],
+        _ => [chroma, 0.0, x],
+    };
+
+    [red + m, green + m, blue + m]
+}
+
+fn oklch_to_rgb(polar: Polar) -> [f64; 3] {
+    let hue = polar.hue.to_radians();
+    let lab = [
+        polar.lightness,
+        polar.saturation * hue.cos(),
+        polar.saturation * hue.sin(),
+    ];
+
+    oklab_to_linear_srgb(lab).map(linear_to_srgb)
+}
+
+fn srgb_to_linear(channel: f64) -> f64 {
+    if channel <= 0.040_45 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(channel: f64) -> f64 {
+    if channel <= 0.003_130_8 {
+        channel * 12.92
+    } else {
+        1.055f64.mul_add(channel.powf(1.0 / 2.4), -0.055)
+    }
+}
+
+// Matrices from Björn Ottosson's definition of OKLab (https://bottosson.github.io/posts/oklab/)
+const SRGB_TO_LMS: [[f64; 3]; 3] = [
+    [0.412_221_470_8, 0.536_332_536_3, 0.051_445_992_9],
+    [0.211_903_498_2, 0.680_699_545_1, 0.107_396_956_6],
+    [0.088_302_461_9, 0.281_718_837_6, 0.629_978_700_5],
+];
+
+const LMS_TO_OKLAB: [[f64; 3]; 3] = [
+    [0.210_454_255_3, 0.793_617_785_0, -0.004_072_046_8],
+    [1.977_998_495_1, -2.428_592_205_0, 0.450_593_709_9],
+    [0.025_904_037_1, 0.782_771_766_2, -0.808_675_766_0],
+];
+
+const OKLAB_TO_LMS: [[f64; 3]; 3] = [
+    [1.0, 0.396_337_777_4, 0.215_803_757_3],
+    [1.0, -0.105_561_345_8, -0.063_854_172_8],
+    [1.0, -0.089_484_177_5, -1.291_485_548_0],
+];
+
+const LMS_TO_SRGB: [[f64; 3]; 3] = [
+    [4.076_741_662_1, -3.307_711_591_3, 0.230_969_929_2],
+    [-1.268_438_004_6, 2.609_757_401_1, -0.341_319_396_5],
+    [-0.004_196_086_3, -0.703_418_614_7, 1.707_614_701_0],
+];
+
+fn multiply(matrix: &[[f64; 3]; 3], vector: [f64; 3]) -> [f64; 3] {
+    matrix.map(|row| row[0].mul_add(vector[0], row[1].mul_add(vector[1], row[2] * vector[2])))
+}
+
+fn linear_srgb_to_oklab(rgb: [f64; 3]) -> [f64; 3] {
+    multiply(&LMS_TO_OKLAB, multiply(&SRGB_TO_LMS, rgb).map(f64::cbrt))
+}
+
+fn oklab_to_linear_srgb(lab: [f64; 3]) -> [f64; 3] {
+    multiply(
+        &LMS_TO_SRGB,
+        multiply(&OKLAB_TO_LMS, lab).map(|x| x.powi(3)),
+    )
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    fn colour(hex: &str) -> Colour {
+        Colour::from_hex(hex).expect("colour should be valid")
+    }
+
+    const COLOURS: [&str; 8] = [
+        "#000000", "#FFFFFF", "#808080", "#FF0000", "#48FFD1", "#3366CC", "#A9B1D6", "#1A1B26",
+    ];
+
+    #[test]
+    fn from_hex_forms() {
+        assert_eq!(colour("#fff"), colour("#FFFFFF"));
+        assert_eq!(colour("#ffff"), colour("#FFFFFF"));
+        assert_eq!(colour("#abc"), colour("#AABBCC"));
+        assert_eq!(colour("#AABBCC80").to_hex(), "#AABBCC80");
+        assert_eq!(colour("#aabbccff").to_hex(), "#AABBCC");
+
+        for invalid in ["fff", "#ff", "#fffff", "#ggg", "#", "#FFFFFFFFF"] {
+            assert!(Colour::from_hex(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        for space in [ColourSpace::Hsl, ColourSpace::Oklch] {
+            for hex in COLOURS {
+                let c = colour(hex);
+                assert_eq!(c.lighten(0.0, space).to_hex(), hex, "{hex} in {space:?}");
+                assert_eq!(c.saturate(0.0, space).to_hex(), hex, "{hex} in {space:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn hsl_complement_round_trips() {
+        // Only in HSL, since the complement of a colour in OKLCH can be outside of sRGB, and is clipped
+        for hex in COLOURS {
+            let c = colour(hex);
+            assert_eq!(
+                c.complement(ColourSpace::Hsl)
+                    .complement(ColourSpace::Hsl)
+                    .to_hex(),
+                hex
+            );
+        }
+    }
+
+    #[test]
+    fn lighten_and_darken() {
+        assert_eq!(
+            colour("#000000").lighten(1.0, ColourSpace::Hsl).to_hex(),
+            "#FFFFFF"
+        );
+        assert_eq!(
+            colour("#000000").lighten(1.0, ColourSpace::Oklch).to_hex(),
+            "#FFFFFF"
+        );
+        assert_eq!(
+            colour("#FFFFFF").lighten(-0.5, ColourSpace::Hsl).to_hex(),
+            "#808080"
+        );
+        assert_eq!(
+            colour("#3366CC").lighten(0.5, ColourSpace::Hsl).to_hex(),
+            "#FFFFFF"
+        );
+    }
+
+    #[test]
+    fn complement() {
+        assert_eq!(
+            colour("#FF0000").complement(ColourSpace::Hsl).to_hex(),
+            "#00FFFF"
+        );
+        assert_eq!(
+            colour("#3366CC").complement(ColourSpace::Hsl).to_hex(),
+            "#CC9933"
+        );
+        // A grey has no hue, so it is its own complement
+        assert_eq!(
+            colour("#808080").complement(ColourSpace::Hsl).to_hex(),
+            "#808080"
+        );
+        assert_eq!(
+            colour("#808080").complement(ColourSpace::Oklch).to_hex(),
+            "#808080"
+        );
+    }
+
+    #[test]
+    fn invert() {
+        assert_eq!(colour("#000000").invert().to_hex(), "#FFFFFF");
+        assert_eq!(colour("#3366CC80").invert().to_hex(), "#CC993380");
+    }
+
+    #[test]
+    fn mix() {
+        let (black, white) = (colour("#000000"), colour("#FFFFFF"));
+        assert_eq!(black.mix(white, 0.5, ColourSpace::Hsl).to_hex(), "#808080");
+        assert_eq!(black.mix(white, 0.0, ColourSpace::Hsl), black);
+        assert_eq!(black.mix(white, 1.0, ColourSpace::Hsl), white);
+
+        // Red and blue meet at magenta, the shortest way around the hue circle
+        assert_eq!(
+            colour("#FF0000")
+                .mix(colour("#0000FF"), 0.5, ColourSpace::Hsl)
+                .to_hex(),
+            "#FF00FF"
+        );
+        // Going from hue 350 to hue 10 passes through red (0), rather than cyan (180)
+        assert_eq!(
+            colour("#FF002B")
+                .mix(colour("#FF2B00"), 0.5, ColourSpace::Hsl)
+                .to_hex(),
+            "#FF0000"
+        );
+        // Mixing with a grey keeps the hue of the other colour
+        assert_eq!(
+            colour("#808080")
+                .mix(colour("#FF0000"), 0.5, ColourSpace::Hsl)
+                .to_hex(),
+            "#BF4040"
+        );
+        // The alpha is mixed as well
+        assert_eq!(
+            colour("#FFFFFF00")
+                .mix(white, 0.5, ColourSpace::Oklch)
+                .to_hex(),
+            "#FFFFFF80"
+        );
+    }
+
+    #[test]
+    fn with_alpha() {
+        assert_eq!(colour("#112233").with_alpha(0.5).to_hex(), "#11223380");
+        assert_eq!(colour("#11223380").with_alpha(1.0).to_hex(), "#112233");
+        assert_eq!(colour("#112233").with_alpha(2.0).to_hex(), "#112233");
+        assert_eq!(colour("#112233").with_alpha(0.0).to_hex(), "#11223300");
+    }
+
+    #[test]
+    fn hex_style() {
+        // Shortened, where each pair of digits is the same
+        assert_eq!(match_hex_style("#AABBCC", "#fff"), "#abc");
+        assert_eq!(match_hex_style("#AABBCC88", "#ffff"), "#abc8");
+        assert_eq!(match_hex_style("#AABBCD", "#fff"), "#aabbcd");
+        // Lengthened
+        assert_eq!(match_hex_style("#abc", "#FFFFFF"), "#AABBCC");
+        assert_eq!(match_hex_style("#abc8", "#000000"), "#aabbcc88");
+        // The case is only changed if the old colour was all one case
+        assert_eq!(match_hex_style("#AbCdEf", "#aBcDeF"), "#AbCdEf");
+        assert_eq!(match_hex_style("#abcdef", "#123456"), "#abcdef");
+    }
+
+    #[test]
+    fn formats() {
+        let c = colour("#9549FFCC");
+        let render = |name: &str| {
+            name.parse::<ColourFormat>()
+                .expect("format should exist")
+                .render(c)
+        };
+
+        assert_eq!(render("hex"), "#9549FFCC");
+        assert_eq!(render("hex-bare"), "9549FF");
+        assert_eq!(render("rgb"), "rgb(149, 73, 255)");
+        assert_eq!(render("ansi"), "149;73;255");
+        assert_eq!(render("argb"), "#CC9549FF");
+    }
+
+    #[test]
+    fn formats_keep_the_old_alpha() {
+        let render = |format: ColourFormat, hex: &str, old_text: &str| {
+            format.render_replacing(colour(hex), Some(old_text))
+        };
+
+        assert_eq!(
+            render(ColourFormat::Rgba, "#336699", "rgba(1, 2, 3, 0.5)"),
+            "rgba(51, 102, 153, 0.5)"
+        );
+        assert_eq!(
+            render(ColourFormat::Float, "#336699", "0.1, 0.2, 0.3, 0.5"),
+            "0.2, 0.4, 0.6, 0.5"
+        );
+        assert_eq!(
+            render(ColourFormat::Argb, "#336699", "#80010203"),
+            "#80336699"
+        );
+        assert_eq!(
+            render(ColourFormat::Hex, "#336699", "#01020380"),
+            "#33669980"
+        );
+
+        // The new colour's own alpha is used if it has one, and opaque colours stay opaque
+        assert_eq!(
+            render(ColourFormat::Rgba, "#336699CC", "rgba(1, 2, 3, 0.5)"),
+            "rgba(51, 102, 153, 0.8)"
+        );
+        assert_eq!(
+            render(ColourFormat::Float, "#336699", "0.1, 0.2, 0.3"),
+            "0.2, 0.4, 0.6"
+        );
+        assert_eq!(
+            render(ColourFormat::Rgb, "#336699", "rgb(1, 2, 3)"),
+            "rgb(51, 102, 153)"
+        );
+    }
+}
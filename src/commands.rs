@@ -0,0 +1,273 @@
+use std::process::ExitCode;
+
+use regex::Regex;
+
+use crate::backup::{get_backups_dir, list_snapshots, prune_snapshots, undo_last_snapshot};
+use crate::cli::{Command, cli, info, print_help};
+use crate::config::{CONFIG_FILE_PATH, config};
+use crate::errors::{DotfilesError, EXIT_CHANGES_PENDING};
+use crate::file::{Mode, PendingFile, modify_files, modify_files_with, open_file};
+use crate::schema::{check_schema, find_missing_keys};
+use crate::scheme::import_scheme;
+
+pub fn run_command(command: &Command) -> Result<ExitCode, DotfilesError> {
+    match command {
+        Command::Apply if cli().dry_run => return diff_files(),
+        Command::Apply => {
+            modify_files(Mode::Apply)?;
+        }
+        Command::Check => return check_files(),
+        Command::Diff => return diff_files(),
+        Command::ListThemes => list_themes()?,
+        Command::ShowTheme(name) => show_theme(name)?,
+        Command::Switch(name) => return switch_theme(name),
+        Command::Import(path) => import_scheme(path)?,
+        Command::Undo => undo_last_snapshot()?,
+        Command::ListBackups => list_backups()?,
+        Command::PruneBackups => {
+            let removed = prune_snapshots()?;
+            info!("Removed {removed} backup(s)");
+        }
+        Command::Help => print_help(),
+        Command::Version => println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn check_themes() -> Result<(), DotfilesError> {
+    let config = config()?;
+
+    // Report every theme which doesn't match the schema, rather than stopping at the first one
+    let mut invalid_themes = 0;
+    for theme in &config.themes {
+        if let Err(e) = check_schema(theme, &config.schema) {
+            eprintln!("error: {e}");
+            invalid_themes += 1;
+        }
+    }
+
+    // Variables which only some themes have will fail in templates when another theme is used
+    for (key, missing_from) in find_missing_keys(&config.themes, &config.schemes, &config.schema) {
+        eprintln!(
+            "warning: Variable '{key}' is in some themes, but not in: {}",
+            missing_from.join(", ")
+        );
+    }
+
+    if invalid_themes == 0 {
+        Ok(())
+    } else {
+        Err(DotfilesError::InvalidThemes(invalid_themes))
+    }
+}
+
+fn check_files() -> Result<ExitCode, DotfilesError> {
+    // Check every theme first, since files are only checked against the current theme
+    check_themes()?;
+
+    let changed_files = modify_files(Mode::Check)?;
+
+    if changed_files.is_empty() {
+        info!("All files are up to date with theme '{}'", config()?.theme);
+
+        Ok(ExitCode::SUCCESS)
+    } else {
+        info!("{} file(s) would be modified:", changed_files.len());
+        for path in &changed_files {
+            info!("\t{path}");
+        }
+
+        Ok(ExitCode::from(EXIT_CHANGES_PENDING))
+    }
+}
+
+fn diff_files() -> Result<ExitCode, DotfilesError> {
+    // Fail if anything would change, so this can be used to check that the files haven't drifted from the theme
+    if modify_files(Mode::Diff)?.is_empty() {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::from(EXIT_CHANGES_PENDING))
+    }
+}
+
+fn list_themes() -> Result<(), DotfilesError> {
+    let config = config()?;
+
+    // Mark the theme which is currently in use
+    for name in config.get_theme_names() {
+        let marker = if name == config.theme { '*' } else { ' ' };
+        println!("{marker} {name}");
+    }
+
+    Ok(())
+}
+
+fn show_theme(name: &str) -> Result<(), DotfilesError> {
+    let config = config()?;
+    let themes = config.get_theme_hashmap();
+    let Some(theme) = themes.get(name) else {
+        return Err(DotfilesError::ThemeNotFound {
+            name: name.to_string(),
+            themes: config.get_theme_names(),
+        });
+    };
+
+    // Print the variables in a consistent order
+    let mut variables = theme
+        .iter()
+        .filter(|(key, _)| key.as_str() != "name")
+        .collect::<Vec<_>>();
+    variables.sort_by_key(|(key, _)| *key);
+
+    // Mark the variables which were inherited from another theme
+    let inherited = config.inherited.get(name);
+
+    println!("{name}");
+    for (key, value) in variables {
+        match inherited.and_then(|inherited| inherited.get(key)) {
+            Some(parent) => println!("\t{key} = {value}  (From '{parent}')"),
+            None => println!("\t{key} = {value}"),
+        }
+    }
+
+    Ok(())
+}
+
+fn list_backups() -> Result<(), DotfilesError> {
+    let snapshots = list_snapshots()?;
+    if snapshots.is_empty() {
+        info!("There are no backups in {}", get_backups_dir()?.display());
+    }
+
+    for snapshot in snapshots {
+        println!(
+            "{}  (Theme '{}', {} file(s))",
+            snapshot.name(),
+            snapshot.manifest.theme,
+            snapshot.manifest.files.len()
+        );
+        for entry in &snapshot.manifest.files {
+            println!("\t{}", entry.path);
+        }
+    }
+
+    Ok(())
+}
+
+fn switch_theme(name: &str) -> Result<ExitCode, DotfilesError> {
+    let config = config()?;
+
+    // Make sure the theme exists before modifying the config
+    if !config.get_theme_hashmap().contains_key(name) {
+        return Err(DotfilesError::ThemeNotFound {
+            name: name.to_string(),
+            themes: config.get_theme_names(),
+        });
+    }
+
+    // Only show what would change with --dry-run, leaving the config alone
+    if cli().dry_run {
+        return diff_files();
+    }
+
+    // Change the config along with the files (The config's theme has already been overridden by main), so it is only changed if the theme applies, and is part of the backup for undo
+    let config_text = open_file(CONFIG_FILE_PATH.as_str())?;
+    let new_config_text = set_config_theme(&config_text, name)?;
+    let config_change = if new_config_text == config_text {
+        Vec::new()
+    } else {
+        vec![PendingFile {
+            path: CONFIG_FILE_PATH.to_string(),
+            old_contents: config_text,
+            new_contents: new_config_text,
+        }]
+    };
+    modify_files_with(Mode::Apply, config_change)?;
+
+    info!("Switched to theme '{name}'");
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn set_config_theme(config_text: &str, name: &str) -> Result<String, DotfilesError> {
+    // Only look at the top-level keys, which come before the first table header (So a "theme" key in a table isn't changed)
+    let top_level_end = Regex::new(r"(?m)^\s*\[")?
+        .find(config_text)
+        .map_or(config_text.len(), |header| header.start());
+    let top_level_text = &config_text[..top_level_end];
+
+    // Let TOML quote the theme name, in case it contains any special characters
+    let quoted_name = toml::Value::String(name.to_string()).to_string();
+
+    let theme_regex = Regex::new(r#"(?m)^(\s*theme\s*=\s*)(?:"(?:\\.|[^"\\])*"|'[^']*')"#)?;
+    if let Some(captures) = theme_regex.captures(top_level_text) {
+        let (Some(whole_match), Some(key_text)) = (captures.get(0), captures.get(1)) else {
+            return Err(DotfilesError::CaptureFail {
+                captures: format!("{captures:?}"),
+                index: 1,
+            });
+        };
+
+        Ok(format!(
+            "{}{}{quoted_name}{}",
+            &config_text[..whole_match.start()],
+            key_text.as_str(),
+            &config_text[whole_match.end()..]
+        ))
+    } else {
+        // There is no theme key yet, so add one at the top of the config
+        Ok(format!("theme = {quoted_name}\n{config_text}"))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    fn set_theme(config_text: &str) -> String {
+        set_config_theme(config_text, "new").expect("theme should be set")
+    }
+
+    #[test]
+    fn theme_after_comments() {
+        assert_eq!(
+            set_theme("# theme = \"commented\"\n\ntheme = \"old\" # current\nbackup_count = 3\n"),
+            "# theme = \"commented\"\n\ntheme = \"new\" # current\nbackup_count = 3\n"
+        );
+    }
+
+    #[test]
+    fn single_quoted_theme() {
+        assert_eq!(set_theme("  theme='old'\n"), "  theme=\"new\"\n");
+        assert_eq!(
+            set_theme("theme = \"with \\\" quote\"\n"),
+            "theme = \"new\"\n"
+        );
+    }
+
+    #[test]
+    fn theme_in_a_table_is_left_alone() {
+        assert_eq!(
+            set_theme("theme = \"old\"\n\n[[files]]\ntheme = \"file\"\n"),
+            "theme = \"new\"\n\n[[files]]\ntheme = \"file\"\n"
+        );
+    }
+
+    #[test]
+    fn missing_theme_is_added() {
+        assert_eq!(
+            set_theme("[[files]]\ntheme = \"file\"\n"),
+            "theme = \"new\"\n[[files]]\ntheme = \"file\"\n"
+        );
+    }
+
+    #[test]
+    fn theme_names_are_quoted() {
+        assert_eq!(
+            set_config_theme("theme = \"old\"\n", "a \"b\"").expect("theme should be set"),
+            "theme = 'a \"b\"'\n"
+        );
+    }
+}
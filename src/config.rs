@@ -1,16 +1,42 @@
 use serde::Deserialize;
-use std::{collections::HashMap, sync::LazyLock};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::LazyLock,
+};
 
-use crate::{errors::DotfilesError, file::open_file};
+use crate::{
+    cli::cli,
+    colour::HexStyle,
+    errors::DotfilesError,
+    expression::Value,
+    file::{expand_path, open_file, resolve_path},
+    schema::Schema,
+    scheme::load_scheme_files,
+    theme::{
+        InheritedKeys, Theme, ThemeSource, check_duplicate_themes, evaluate_theme,
+        load_theme_files, resolve_inheritance, theme_from_toml, theme_name,
+    },
+};
 
 const CONFIG_FILE_SUB_PATH: &str = "dotfile-templater/config.toml";
+const THEMES_DIR_SUB_PATH: &str = "dotfile-templater/themes";
+const SCHEMES_DIR_SUB_PATH: &str = "dotfile-templater/schemes";
 
+// Use $XDG_CONFIG_HOME, falling back to its default of ~/.config (Or .config in the current folder, if HOME isn't set either)
 pub static XDG_CONFIG_PATH: LazyLock<String> = LazyLock::new(|| {
-    std::env::vars().collect::<HashMap<String, String>>()["XDG_CONFIG_HOME"].clone()
+    std::env::var("XDG_CONFIG_HOME")
+        .or_else(|_| std::env::var("HOME").map(|home| format!("{home}/.config")))
+        .unwrap_or_else(|_| ".config".to_string())
 });
 
-static CONFIG_FILE_PATH: LazyLock<String> =
-    LazyLock::new(|| format!("{}/{CONFIG_FILE_SUB_PATH}", *XDG_CONFIG_PATH));
+// The config file can be chosen with --config, otherwise it is in the XDG config folder
+pub static CONFIG_FILE_PATH: LazyLock<String> = LazyLock::new(|| {
+    cli()
+        .config_path
+        .clone()
+        .unwrap_or_else(|| format!("{}/{CONFIG_FILE_SUB_PATH}", *XDG_CONFIG_PATH))
+});
 
 pub const FUNCTION_CHAR: char = '@';
 
@@ -18,6 +44,10 @@ const fn get_default_marker_repetition_num() -> usize {
     3
 }
 
+const fn get_default_backup_count() -> usize {
+    10
+}
+
 #[allow(dead_code)]
 #[derive(Deserialize, Debug, Clone)]
 pub struct Config {
@@ -25,41 +55,201 @@ pub struct Config {
     #[serde(default = "get_default_marker_repetition_num")]
     pub marker_repetition_num: usize,
     pub files: Vec<FileConfig>,
+    #[serde(default = "get_default_backup_count")]
+    pub backup_count: usize,
+
+    // The themes as they are written in the config, which are read into themes
+    #[serde(default, rename = "themes")]
+    theme_tables: Vec<toml::Table>,
+    #[serde(skip)]
+    pub themes: Vec<Theme>,
+    // Folder of theme files, which are added to the themes (Defaults to the themes folder next to the config)
+    pub themes_dir: Option<String>,
+    // Folder of base16/base24 schemes, which are added to the themes (Defaults to the schemes folder next to the config)
+    pub schemes_dir: Option<String>,
+    // Variables which are added to every theme that has the variable they point to, such as bg_col = "base00"
+    #[serde(default)]
+    pub aliases: HashMap<String, String>,
 
+    // The variables which every theme has to have, and their types, such as bg_col = "colour"
     #[serde(default)]
-    pub themes: Vec<HashMap<String, String>>,
+    pub schema: Schema,
+
+    // For each theme, the variables which it inherited with extends, and the theme each one came from
+    #[serde(skip)]
+    pub inherited: HashMap<String, InheritedKeys>,
+    // The names of the themes which were loaded from base16/base24 schemes
+    #[serde(skip)]
+    pub schemes: HashSet<String>,
 }
 
 impl Config {
-    pub fn get_theme_hashmap(&self) -> HashMap<String, HashMap<String, String>> {
+    pub fn get_theme_hashmap(&self) -> HashMap<String, Theme> {
         self.themes
             .iter()
-            .map(|theme| (theme["name"].clone(), theme.clone()))
+            .map(|theme| (theme_name(theme), theme.clone()))
             .collect()
     }
+
+    pub fn get_theme_names(&self) -> Vec<String> {
+        self.themes.iter().map(theme_name).collect()
+    }
+
+    pub fn get_themes_dir(&self) -> String {
+        folder_path(self.themes_dir.as_deref(), THEMES_DIR_SUB_PATH)
+    }
+
+    pub fn get_schemes_dir(&self) -> String {
+        folder_path(self.schemes_dir.as_deref(), SCHEMES_DIR_SUB_PATH)
+    }
+}
+
+fn folder_path(setting: Option<&str>, default_sub_path: &str) -> String {
+    setting.map_or_else(
+        || format!("{}/{default_sub_path}", *XDG_CONFIG_PATH),
+        resolve_path,
+    )
 }
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct FileConfig {
+    // A file, a glob such as waybar/**/*.css, or a folder
     pub file: String,
     pub marker_char: String,
+    #[serde(default)]
+    pub hex_style: HexStyle,
+    // For a folder, the files within it to template (Everything by default), and the files to leave alone
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    // A theme which this file always uses, instead of the theme in the config
+    pub theme: Option<String>,
+    // Variables which are added to the theme (Or which replace its variables) for this file only
+    #[serde(default)]
+    vars: toml::Table,
+
+    // The file's own theme, with its variables, if it has a theme or variables
+    #[serde(skip)]
+    pub file_theme: Option<Theme>,
 }
 
-pub static CONFIG: LazyLock<Config> = LazyLock::new(parse_config);
+// The config is only read once, keeping the error (Rather than exiting) so that main can report it
+pub static CONFIG: LazyLock<Result<Config, DotfilesError>> = LazyLock::new(parse_config);
 
-fn parse_config() -> Config {
-    // Read the TOML config into a Config struct (Exit the program if the config cannot be parsed)
-    match open_file(CONFIG_FILE_PATH.as_str()) {
-        Ok(config) => match toml::from_str(config.as_str()) {
-            Ok(config) => return config,
-            Err(e) => {
-                eprintln!("{}", Into::<DotfilesError>::into(e));
+pub fn config() -> Result<&'static Config, DotfilesError> {
+    CONFIG.as_ref().map_err(Clone::clone)
+}
+
+fn parse_config() -> Result<Config, DotfilesError> {
+    // Read the TOML config into a Config struct
+    let mut config = open_file(CONFIG_FILE_PATH.as_str())
+        .and_then(|config| Ok(toml::from_str::<Config>(config.as_str())?))
+        .map_err(|e| DotfilesError::ConfigError {
+            path: CONFIG_FILE_PATH.clone(),
+            error: Box::new(e),
+        })?;
+
+    // Allow the theme to be overridden for this run with --theme
+    if let Some(theme) = &cli().theme {
+        config.theme.clone_from(theme);
+    }
+
+    let config_error = |e| DotfilesError::ConfigError {
+        path: CONFIG_FILE_PATH.clone(),
+        error: Box::new(e),
+    };
+
+    // Expand ~ and environment variables in the paths, such as ~/.Xresources or ${XDG_DATA_HOME:-~/.local/share}/...
+    for dir in [&mut config.themes_dir, &mut config.schemes_dir]
+        .into_iter()
+        .flatten()
+    {
+        *dir = expand_path(dir).map_err(config_error)?;
+    }
+    for file_config in &mut config.files {
+        file_config.file = expand_path(&file_config.file).map_err(config_error)?;
+    }
+
+    // Folders which were set in the config have to exist, the default ones don't
+    for (setting, dir) in [
+        (&config.themes_dir, config.get_themes_dir()),
+        (&config.schemes_dir, config.get_schemes_dir()),
+    ] {
+        if setting.is_some() && !Path::new(&dir).is_dir() {
+            return Err(config_error(DotfilesError::FileReadError(format!(
+                "Folder '{dir}' does not exist"
+            ))));
+        }
+    }
+
+    // Add the themes from the themes and schemes folders, making sure no two themes have the same name
+    let mut theme_sources = config
+        .theme_tables
+        .iter()
+        .map(|table| ThemeSource {
+            theme: theme_from_toml(table.clone()),
+            source: "the config".to_string(),
+        })
+        .collect::<Vec<_>>();
+    theme_sources
+        .extend(load_theme_files(Path::new(&config.get_themes_dir())).map_err(config_error)?);
+    let scheme_sources =
+        load_scheme_files(Path::new(&config.get_schemes_dir())).map_err(config_error)?;
+    config.schemes = scheme_sources
+        .iter()
+        .map(|source| theme_name(&source.theme))
+        .collect();
+    theme_sources.extend(scheme_sources);
+    check_duplicate_themes(&theme_sources).map_err(config_error)?;
+    config.themes = theme_sources
+        .into_iter()
+        .map(|source| source.theme)
+        .collect();
+
+    // Fill in the variables which each theme inherits with extends
+    let (mut themes, inherited) = resolve_inheritance(&config.themes).map_err(config_error)?;
+    config.inherited = inherited;
+
+    // Add the aliases to the themes which have the variable they point to (Unless the theme already has the alias)
+    for theme in &mut themes {
+        for (alias, key) in &config.aliases {
+            if theme.contains_key(key) && !theme.contains_key(alias) {
+                theme.insert(alias.clone(), Value::String(format!("${{{key}}}")));
             }
-        },
-        Err(e) => {
-            eprintln!("{e}");
         }
     }
 
-    std::process::exit(0x1000)
+    // Work out the values which use other values, such as lighten(primary_col, 10%) or "${font} Bold" (After inheriting, so they use the theme's own variables)
+    config.themes = themes
+        .iter()
+        .map(evaluate_theme)
+        .collect::<Result<_, _>>()
+        .map_err(config_error)?;
+
+    // Give the files which have their own theme or variables their own copy of the theme (Before it is worked out, so variables which use a replaced variable use the file's value)
+    let themes_by_name = themes
+        .iter()
+        .map(|theme| (theme_name(theme), theme))
+        .collect::<HashMap<_, _>>();
+    for file_config in &mut config.files {
+        if file_config.theme.is_none() && file_config.vars.is_empty() {
+            continue;
+        }
+
+        let name = file_config.theme.as_ref().unwrap_or(&config.theme);
+        let Some(theme) = themes_by_name.get(name) else {
+            return Err(config_error(DotfilesError::ThemeNotFound {
+                name: name.clone(),
+                themes: themes_by_name.keys().cloned().collect(),
+            }));
+        };
+
+        let mut file_theme = (*theme).clone();
+        file_theme.extend(theme_from_toml(file_config.vars.clone()));
+        file_theme.insert("name".to_string(), Value::String(name.clone()));
+        file_config.file_theme = Some(evaluate_theme(&file_theme).map_err(config_error)?);
+    }
+
+    Ok(config)
 }
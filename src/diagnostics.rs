@@ -0,0 +1,213 @@
+use std::fmt::Display;
+use std::ops::Range;
+
+use crate::errors::{DotfilesError, EXIT_TEMPLATE_ERROR};
+
+#[derive(Debug, Clone)]
+pub struct Location {
+    // Line and column of the start of the offending text (Both start at 1)
+    pub line: usize,
+    pub column: usize,
+    // Number of characters to underline
+    pub width: usize,
+    pub line_text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub path: String,
+    // Where the offending template is, if the error came from a template
+    pub location: Option<Location>,
+    pub error: DotfilesError,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "error: {}", self.error)?;
+
+        let Some(location) = &self.location else {
+            return write!(f, "  --> {}", self.path);
+        };
+
+        // Render the source line with a caret under the offending text, in the style of rustc
+        let gutter = " ".repeat(location.line.to_string().len());
+        writeln!(
+            f,
+            "{gutter}--> {}:{}:{}",
+            self.path, location.line, location.column
+        )?;
+        writeln!(f, "{gutter} |")?;
+        writeln!(f, "{} | {}", location.line, location.line_text)?;
+
+        // Keep any tabs before the caret, so it lines up with the text above it
+        let padding = location
+            .line_text
+            .chars()
+            .take(location.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        write!(
+            f,
+            "{gutter} | {padding}{}",
+            "^".repeat(location.width.max(1))
+        )?;
+
+        if let Some(help) = self.error.help() {
+            write!(f, " {help}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    pub errors: Vec<Diagnostic>,
+
+    // The file which is currently being templated, and the byte offset of the start of each of its lines
+    current_path: String,
+    current_contents: String,
+    line_starts: Vec<usize>,
+}
+
+impl Diagnostics {
+    pub fn set_file(&mut self, path: &str, contents: &str) {
+        self.current_path = path.to_string();
+        self.current_contents = contents.to_string();
+        self.line_starts = std::iter::once(0)
+            .chain(contents.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+    }
+
+    // Record an error at a range of bytes within the current file (Using the range attached to the error instead, if it has one)
+    pub fn error_at(&mut self, range: Range<usize>, error: DotfilesError) {
+        let (range, error) = match error {
+            DotfilesError::Spanned { range, error } => (range, *error),
+            error => (range, error),
+        };
+
+        let line_index = self
+            .line_starts
+            .partition_point(|&start| start <= range.start)
+            .saturating_sub(1);
+        let line_start = self.line_starts.get(line_index).copied().unwrap_or(0);
+        let line_end = self
+            .line_starts
+            .get(line_index + 1)
+            .map_or(self.current_contents.len(), |next_start| next_start - 1);
+
+        // Only underline the part of the range which is on this line
+        let char_count = |bytes: Range<usize>| {
+            self.current_contents
+                .get(bytes)
+                .map_or(0, |text| text.chars().count())
+        };
+
+        let location = Location {
+            line: line_index + 1,
+            column: char_count(line_start..range.start) + 1,
+            width: char_count(range.start..range.end.min(line_end)),
+            line_text: self
+                .current_contents
+                .get(line_start..line_end)
+                .unwrap_or_default()
+                .trim_end_matches('\r')
+                .to_string(),
+        };
+
+        self.errors.push(Diagnostic {
+            path: self.current_path.clone(),
+            location: Some(location),
+            error,
+        });
+    }
+
+    // Record an error which applies to the whole of the current file
+    pub fn error(&mut self, error: DotfilesError) {
+        self.errors.push(Diagnostic {
+            path: self.current_path.clone(),
+            location: None,
+            error,
+        });
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub const fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    pub fn file_count(&self) -> usize {
+        let mut paths = self
+            .errors
+            .iter()
+            .map(|diagnostic| diagnostic.path.as_str())
+            .collect::<Vec<_>>();
+        paths.sort_unstable();
+        paths.dedup();
+
+        paths.len()
+    }
+
+    pub fn print_summary(&self) {
+        for diagnostic in &self.errors {
+            eprintln!("{diagnostic}\n");
+        }
+    }
+
+    // Turn the collected errors into a single error, if there were any
+    pub fn into_result(self) -> Result<(), DotfilesError> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            self.print_summary();
+
+            Err(DotfilesError::TemplateErrors {
+                count: self.errors.len(),
+                files: self.file_count(),
+                exit_code: self
+                    .errors
+                    .iter()
+                    .map(|diagnostic| diagnostic.error.exit_code())
+                    .max()
+                    .unwrap_or(EXIT_TEMPLATE_ERROR),
+            })
+        }
+    }
+}
+
+pub fn closest_match<'a, I: IntoIterator<Item = &'a str>>(
+    word: &str,
+    candidates: I,
+) -> Option<String> {
+    // Only suggest names which are close enough to be a typo
+    let max_distance = (word.chars().count() / 3).max(1);
+
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(word, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate.to_string())
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    // Levenshtein distance, keeping only the previous row of the table
+    let b_chars = b.chars().collect::<Vec<_>>();
+    let mut previous_row = (0..=b_chars.len()).collect::<Vec<_>>();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut row = vec![i + 1; b_chars.len() + 1];
+        for (j, b_char) in b_chars.iter().enumerate() {
+            let substitution_cost = usize::from(a_char != *b_char);
+            row[j + 1] = (previous_row[j] + substitution_cost)
+                .min(previous_row[j + 1] + 1)
+                .min(row[j] + 1);
+        }
+        previous_row = row;
+    }
+
+    previous_row[b_chars.len()]
+}
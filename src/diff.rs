@@ -0,0 +1,402 @@
+use std::fmt::Write;
+use std::io::IsTerminal;
+
+// The number of unchanged lines shown around each change
+const CONTEXT_LINES: usize = 3;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineChange {
+    Same { old_index: usize, new_index: usize },
+    Removed { old_index: usize },
+    Added { new_index: usize },
+}
+
+pub fn print_diff(path: &str, old_contents: &str, new_contents: &str) {
+    // Only add colour and line numbers when a person is reading the output (So it can still be used as a patch)
+    let pretty = std::io::stdout().is_terminal();
+
+    print!("{}", unified_diff(path, old_contents, new_contents, pretty));
+}
+
+pub fn unified_diff(path: &str, old_contents: &str, new_contents: &str, pretty: bool) -> String {
+    // Lines keep their line ending, so a last line without one is different to the same line with one
+    let old_lines = old_contents.split_inclusive('\n').collect::<Vec<_>>();
+    let new_lines = new_contents.split_inclusive('\n').collect::<Vec<_>>();
+
+    let changes = diff_lines(&old_lines, &new_lines);
+
+    let mut output = String::new();
+    if pretty {
+        let _ = writeln!(output, "{BOLD}--- {path}\n+++ {path}{RESET}");
+    } else {
+        let _ = writeln!(output, "--- {path}\n+++ {path}");
+    }
+
+    for hunk in group_hunks(&changes) {
+        write_hunk(&mut output, &hunk, &old_lines, &new_lines, pretty);
+    }
+
+    output
+}
+
+fn write_hunk(
+    output: &mut String,
+    hunk: &[LineChange],
+    old_lines: &[&str],
+    new_lines: &[&str],
+    pretty: bool,
+) {
+    // Find where the hunk starts, and how many lines it covers, in each file (Line numbers start at 1)
+    let old_count = hunk
+        .iter()
+        .filter(|change| !matches!(change, LineChange::Added { .. }))
+        .count();
+    let new_count = hunk
+        .iter()
+        .filter(|change| !matches!(change, LineChange::Removed { .. }))
+        .count();
+    let (old_start, new_start) = hunk_start(hunk);
+
+    // An empty range is given the line before it, as in GNU diff
+    let old_start = if old_count == 0 {
+        old_start
+    } else {
+        old_start + 1
+    };
+    let new_start = if new_count == 0 {
+        new_start
+    } else {
+        new_start + 1
+    };
+
+    let header = format!("@@ -{old_start},{old_count} +{new_start},{new_count} @@");
+    if pretty {
+        let _ = writeln!(output, "{CYAN}{header}{RESET}");
+    } else {
+        let _ = writeln!(output, "{header}");
+    }
+
+    for change in hunk {
+        let (old_num, new_num, prefix, line, colour) = match *change {
+            LineChange::Same {
+                old_index,
+                new_index,
+            } => (
+                Some(old_index + 1),
+                Some(new_index + 1),
+                ' ',
+                old_lines[old_index],
+                "",
+            ),
+            LineChange::Removed { old_index } => {
+                (Some(old_index + 1), None, '-', old_lines[old_index], RED)
+            }
+            LineChange::Added { new_index } => {
+                (None, Some(new_index + 1), '+', new_lines[new_index], GREEN)
+            }
+        };
+
+        let (line, has_newline) = line
+            .strip_suffix('\n')
+            .map_or((line, false), |line| (line, true));
+
+        if pretty {
+            let number = |num: Option<usize>| num.map_or_else(String::new, |n| n.to_string());
+            let reset = if colour.is_empty() { "" } else { RESET };
+
+            let _ = writeln!(
+                output,
+                "{colour}{:>5} {:>5} {prefix}{}{reset}",
+                number(old_num),
+                number(new_num),
+                line.trim_end_matches('\r')
+            );
+        } else {
+            let _ = writeln!(output, "{prefix}{line}");
+        }
+
+        // Mark the last line of a file which doesn't end with a newline, so the diff can be used with patch
+        if !has_newline {
+            let _ = writeln!(output, "\\ No newline at end of file");
+        }
+    }
+}
+
+fn hunk_start(hunk: &[LineChange]) -> (usize, usize) {
+    // The first line of a hunk is always either context, or a change with a known position in one of the files
+    match hunk.first() {
+        Some(&LineChange::Same {
+            old_index,
+            new_index,
+        }) => (old_index, new_index),
+        Some(&LineChange::Removed { old_index }) => {
+            // Count the lines added before this point to find the position in the new file
+            let new_index = hunk
+                .iter()
+                .find_map(|change| match change {
+                    LineChange::Same { new_index, .. } | LineChange::Added { new_index } => {
+                        Some(*new_index)
+                    }
+                    LineChange::Removed { .. } => None,
+                })
+                .unwrap_or(0);
+            (old_index, new_index)
+        }
+        Some(&LineChange::Added { new_index }) => (0, new_index),
+        None => (0, 0),
+    }
+}
+
+fn group_hunks(changes: &[LineChange]) -> Vec<Vec<LineChange>> {
+    // Find the positions of every change
+    let changed_positions = changes
+        .iter()
+        .enumerate()
+        .filter(|(_, change)| !matches!(change, LineChange::Same { .. }))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+
+    // Join changes which are close enough that their context would overlap
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for position in changed_positions {
+        let start = position.saturating_sub(CONTEXT_LINES);
+        let end = (position + CONTEXT_LINES + 1).min(changes.len());
+
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| changes[start..end].to_vec())
+        .collect()
+}
+
+fn diff_lines(old_lines: &[&str], new_lines: &[&str]) -> Vec<LineChange> {
+    // Myers' algorithm, finding the shortest edit script between the two lists of lines
+    let old_len = old_lines.len();
+    let new_len = new_lines.len();
+    let max_edits = old_len + new_len;
+    let offset = max_edits + 1;
+
+    // Furthest reaching x position on each diagonal (k = x - y), saving the diagonals which were reached (offset - edits to offset + edits) after each number of edits
+    let mut furthest = vec![0; 2 * max_edits + 3];
+    let mut trace = Vec::new();
+    let mut total_edits = 0;
+
+    'search: for edits in 0..=max_edits {
+        for k in (0..=2 * edits).step_by(2).map(|i| i + offset - edits) {
+            // Either move down (Added line) from diagonal k + 1, or right (Removed line) from diagonal k - 1
+            let mut x = if k == offset - edits
+                || (k != offset + edits && furthest[k - 1] < furthest[k + 1])
+            {
+                furthest[k + 1]
+            } else {
+                furthest[k - 1] + 1
+            };
+            let mut y = x + offset - k;
+
+            // Follow the diagonal while the lines are the same
+            while let (Some(old_line), Some(new_line)) = (old_lines.get(x), new_lines.get(y))
+                && old_line == new_line
+            {
+                x += 1;
+                y += 1;
+            }
+
+            furthest[k] = x;
+
+            if x >= old_len && y >= new_len {
+                total_edits = edits;
+                break 'search;
+            }
+        }
+
+        trace.push(furthest[offset - edits..=offset + edits].to_vec());
+    }
+
+    // Walk back through the saved positions to recover the path
+    let mut changes = Vec::new();
+    let (mut x, mut y) = (old_len, new_len);
+    for edits in (0..=total_edits).rev() {
+        let k = x + offset - y;
+
+        // With no edits the path starts at the first line of both files
+        let (previous_x, previous_y) = if edits == 0 {
+            (0, 0)
+        } else {
+            // The positions with one less edit, where the saved diagonals start at offset - (edits - 1)
+            let furthest = |k: usize| trace[edits - 1][k + edits - 1 - offset];
+            let previous_k = if k == offset - edits
+                || (k != offset + edits && furthest(k - 1) < furthest(k + 1))
+            {
+                k + 1
+            } else {
+                k - 1
+            };
+            let previous_x = furthest(previous_k);
+
+            (previous_x, previous_x + offset - previous_k)
+        };
+
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+            changes.push(LineChange::Same {
+                old_index: x,
+                new_index: y,
+            });
+        }
+
+        if edits > 0 {
+            if x == previous_x {
+                changes.push(LineChange::Added {
+                    new_index: previous_y,
+                });
+            } else {
+                changes.push(LineChange::Removed {
+                    old_index: previous_x,
+                });
+            }
+        }
+
+        x = previous_x;
+        y = previous_y;
+    }
+
+    changes.reverse();
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(old_contents: &str, new_contents: &str) -> String {
+        unified_diff("f", old_contents, new_contents, false)
+    }
+
+    #[test]
+    fn insert() {
+        assert_eq!(
+            diff("a\nb\nc\n", "a\nb\nx\nc\n"),
+            "--- f\n+++ f\n@@ -1,3 +1,4 @@\n a\n b\n+x\n c\n"
+        );
+    }
+
+    #[test]
+    fn delete() {
+        assert_eq!(
+            diff("a\nb\nc\n", "a\nc\n"),
+            "--- f\n+++ f\n@@ -1,3 +1,2 @@\n a\n-b\n c\n"
+        );
+    }
+
+    #[test]
+    fn replace() {
+        assert_eq!(
+            diff("a\nb\nc\n", "a\nB\nc\n"),
+            "--- f\n+++ f\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n"
+        );
+    }
+
+    #[test]
+    fn change_at_start() {
+        assert_eq!(
+            diff("1\n2\n3\n4\n5\n6\n7\n8\n", "X\n2\n3\n4\n5\n6\n7\n8\n"),
+            "--- f\n+++ f\n@@ -1,4 +1,4 @@\n-1\n+X\n 2\n 3\n 4\n"
+        );
+    }
+
+    #[test]
+    fn change_at_end() {
+        assert_eq!(
+            diff("1\n2\n3\n4\n5\n6\n7\n8\n", "1\n2\n3\n4\n5\n6\n7\nX\n"),
+            "--- f\n+++ f\n@@ -5,4 +5,4 @@\n 5\n 6\n 7\n-8\n+X\n"
+        );
+    }
+
+    #[test]
+    fn separate_hunks() {
+        assert_eq!(
+            diff(
+                "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n",
+                "X\n2\n3\n4\n5\n6\n7\n8\n9\nY\n"
+            ),
+            "--- f\n+++ f\n@@ -1,4 +1,4 @@\n-1\n+X\n 2\n 3\n 4\n@@ -7,4 +7,4 @@\n 7\n 8\n 9\n-10\n+Y\n"
+        );
+    }
+
+    #[test]
+    fn insert_into_empty_file() {
+        assert_eq!(diff("", "a\n"), "--- f\n+++ f\n@@ -0,0 +1,1 @@\n+a\n");
+    }
+
+    #[test]
+    fn unchanged() {
+        assert_eq!(diff("a\nb\n", "a\nb\n"), "--- f\n+++ f\n");
+    }
+
+    #[test]
+    fn no_final_newline() {
+        assert_eq!(
+            diff("a\nb", "a\nc"),
+            "--- f\n+++ f\n@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+c\n\\ No newline at end of file\n"
+        );
+    }
+
+    #[test]
+    fn final_newline_added() {
+        assert_eq!(
+            diff("a\nb", "a\nb\n"),
+            "--- f\n+++ f\n@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+b\n"
+        );
+    }
+
+    #[test]
+    fn changes_rebuild_both_files() {
+        let cases = [
+            ("a b c a b b a", "c b a b a c"),
+            ("a b c d e f g", "g f e d c b a"),
+            ("x x x x", "y y"),
+            ("", "a b c"),
+            ("a b c", ""),
+        ];
+
+        for (old, new) in cases {
+            let old_lines = old.split_whitespace().collect::<Vec<_>>();
+            let new_lines = new.split_whitespace().collect::<Vec<_>>();
+            let changes = diff_lines(&old_lines, &new_lines);
+
+            let rebuilt_old = changes
+                .iter()
+                .filter_map(|change| match *change {
+                    LineChange::Same { old_index, .. } | LineChange::Removed { old_index } => {
+                        Some(old_lines[old_index])
+                    }
+                    LineChange::Added { .. } => None,
+                })
+                .collect::<Vec<_>>();
+            let rebuilt_new = changes
+                .iter()
+                .filter_map(|change| match *change {
+                    LineChange::Same { new_index, .. } | LineChange::Added { new_index } => {
+                        Some(new_lines[new_index])
+                    }
+                    LineChange::Removed { .. } => None,
+                })
+                .collect::<Vec<_>>();
+
+            assert_eq!(rebuilt_old, old_lines, "{old} -> {new}");
+            assert_eq!(rebuilt_new, new_lines, "{old} -> {new}");
+        }
+    }
+}
@@ -1,22 +1,44 @@
-use std::collections::HashMap;
+use std::ops::Range;
 
 use thiserror::Error;
 
+// Exit codes for each kind of failure, so scripts can tell them apart (Documented in the README)
+pub const EXIT_CHANGES_PENDING: u8 = 1;
+pub const EXIT_USAGE_ERROR: u8 = 2;
+pub const EXIT_CONFIG_ERROR: u8 = 3;
+pub const EXIT_TEMPLATE_ERROR: u8 = 4;
+pub const EXIT_IO_ERROR: u8 = 5;
+
 #[derive(Error, Debug, Clone)]
 pub enum DotfilesError {
+    #[error("{0} (See --help)")]
+    CliError(String),
+
+    #[error("File '{0}' was given with --file, but it is not in the config")]
+    FileNotInConfig(String),
+
+    #[error("Config '{path}' could not be loaded: {error}")]
+    ConfigError { path: String, error: Box<Self> },
+
     #[error("TOML could not be read to string: {0}")]
     TomlReadError(#[from] toml::de::Error),
 
+    #[error("TOML could not be written: {0}")]
+    TomlWriteError(#[from] toml::ser::Error),
+
     #[error("File could not be read: {0}")]
     FileReadError(String),
 
+    #[error("Glob pattern '{pattern}' is not valid: {error}")]
+    GlobError { pattern: String, error: String },
+
     #[error("Regex failed to be created: {0}")]
     RegexFail(#[from] regex::Error),
 
-    #[error("Regex '{regex_str}' did not match anything in haystack: {hay}")]
+    #[error("Regex '{regex_str}' did not match anything in '{}'", hay.trim())]
     RegexMatchError { regex_str: String, hay: String },
 
-    #[error("Regex '{regex_str}' did not produce {} captures: {hay}", capture_index + 1)]
+    #[error("Regex '{regex_str}' did not produce {} matches in '{}'", capture_index + 1, hay.trim())]
     RegexNthMatchError {
         regex_str: String,
         hay: String,
@@ -26,27 +48,158 @@ pub enum DotfilesError {
     #[error("Regex capture at index {index} could not be found: {captures}")]
     CaptureFail { captures: String, index: usize },
 
+    #[error("Template could not be parsed: {0}")]
+    ParseError(String),
+
+    #[error("Argument {position} of '{name}' should be {expected}, found {found}")]
+    ArgumentTypeError {
+        name: String,
+        position: usize,
+        expected: String,
+        found: String,
+    },
+
     #[error("Function '{name}' needs {needed} args, found {}: {args:?}", args.len())]
     FuncArgumentError {
         name: String,
-        needed: usize,
+        needed: String,
         args: Vec<String>,
     },
 
-    #[error("Tried to replace text with different length string (Length: {}  -->  {}): '{text_to_replace}'    -->    '{replace_text}'", text_to_replace.len(), replace_text.len())]
-    ReplaceTextDifferentLength {
-        text_to_replace: String,
-        replace_text: String,
+    #[error(
+        "Replacement of '{first_text}' (Bytes {first_range:?}) overlaps with replacement of '{second_text}' (Bytes {second_range:?}) in file '{path}'"
+    )]
+    OverlappingEdits {
+        path: String,
+        first_range: Range<usize>,
+        first_text: String,
+        second_range: Range<usize>,
+        second_text: String,
+    },
+
+    #[error("Function '{name}' does not exist, the available functions are: {}", functions.join(", "))]
+    UnknownFunction {
+        name: String,
+        functions: Vec<String>,
+        suggestion: Option<String>,
+    },
+
+    #[error("'{0}' is not a valid hex colour (Expected #RGB, #RGBA, #RRGGBB or #RRGGBBAA)")]
+    InvalidColour(String),
+
+    #[error("'{0}' is not a colour space, use \"hsl\" or \"oklch\"")]
+    UnknownColourSpace(String),
+
+    #[error("'{0}' is not a hex style, use \"exact\" or \"preserve\"")]
+    UnknownHexStyle(String),
+
+    #[error("Colour format '{name}' does not exist, the available formats are: {}", formats.join(", "))]
+    UnknownColourFormat {
+        name: String,
+        formats: Vec<String>,
+        suggestion: Option<String>,
+    },
+
+    #[error("Value of '{key}' in theme '{theme}' could not be evaluated: {error}")]
+    ThemeValueError {
+        theme: String,
+        key: String,
+        error: Box<Self>,
+    },
+
+    #[error("Values in theme '{theme}' depend on each other in a cycle: {}", chain.join(" -> "))]
+    ThemeValueCycle { theme: String, chain: Vec<String> },
+
+    #[error("YAML could not be read: {0}")]
+    YamlReadError(String),
+
+    #[error("Theme '{0}' already exists")]
+    ThemeAlreadyExists(String),
+
+    #[error("Theme file '{0}' already exists")]
+    ThemeFileExists(String),
+
+    #[error("Theme file '{path}' could not be loaded: {error}")]
+    ThemeFileError { path: String, error: Box<Self> },
+
+    #[error("Theme '{name}' is defined in both {first} and {second}")]
+    DuplicateTheme {
+        name: String,
+        first: String,
+        second: String,
+    },
+
+    #[error("Theme '{theme}' extends '{parent}', which was not found")]
+    ParentThemeNotFound {
+        theme: String,
+        parent: String,
+        suggestion: Option<String>,
     },
 
+    #[error("Themes extend each other in a cycle: {}", .0.join(" -> "))]
+    ThemeExtendsCycle(Vec<String>),
+
+    #[error("Theme '{theme}' does not match the schema: {}", problems.join(", "))]
+    SchemaMismatch {
+        theme: String,
+        problems: Vec<String>,
+    },
+
+    #[error("{0} theme(s) do not match the schema")]
+    InvalidThemes(usize),
+
     #[error("Theme '{name}' was not found in themes: {themes:?}")]
     ThemeNotFound { name: String, themes: Vec<String> },
 
-    #[error("Argument '{arg}' not found in theme: {theme_hashmap:?}")]
+    #[error("Argument '{arg}' not found in theme '{theme}'")]
     ArgNotFound {
         arg: String,
-        theme_hashmap: HashMap<String, String>,
+        theme: String,
+        suggestion: Option<String>,
+    },
+
+    #[error("Writing '{path}' failed, so {restored} already written file(s) were restored{}: {error}", if not_restored.is_empty() { String::new() } else { format!(" (These could not be restored, use undo: {not_restored:?})") })]
+    WriteRolledBack {
+        path: String,
+        error: Box<Self>,
+        restored: usize,
+        not_restored: Vec<String>,
+    },
+
+    #[error("Restoring '{path}' failed, so the files which were already restored were put back, and the backup was kept{}: {error}", if not_restored.is_empty() { String::new() } else { format!(" (These could not be put back: {not_restored:?})") })]
+    UndoRolledBack {
+        path: String,
+        error: Box<Self>,
+        not_restored: Vec<String>,
+    },
+
+    #[error("{error}")]
+    Spanned {
+        range: Range<usize>,
+        error: Box<Self>,
+    },
+
+    #[error("Found {count} error(s) in {files} file(s)")]
+    TemplateErrors {
+        count: usize,
+        files: usize,
+        // The exit code of the most severe error, so a file which couldn't be read still exits with EXIT_IO_ERROR
+        exit_code: u8,
     },
+
+    #[error("Environment variable '{0}' is not set")]
+    EnvVarNotFound(String),
+
+    #[error("Path '{path}' uses environment variable '{variable}', which is not set")]
+    UnsetPathVariable { path: String, variable: String },
+
+    #[error("There are no backups to undo")]
+    BackupNotFound,
+
+    #[error(
+        "File '{path}' has been changed since backup '{snapshot}' was made, so it will not be undone"
+    )]
+    BackupFileChanged { path: String, snapshot: String },
 }
 
 impl From<std::io::Error> for DotfilesError {
@@ -54,3 +207,98 @@ impl From<std::io::Error> for DotfilesError {
         Self::FileReadError(value.to_string())
     }
 }
+
+impl DotfilesError {
+    // Attach the position (Within the file) of the text which caused this error, keeping the innermost position if it already has one
+    pub fn at(self, range: &Range<usize>) -> Self {
+        match self {
+            Self::Spanned { .. } => self,
+            error => Self::Spanned {
+                range: range.clone(),
+                error: Box::new(error),
+            },
+        }
+    }
+
+    // The error without the position attached to it
+    pub fn unspanned(self) -> Self {
+        match self {
+            Self::Spanned { error, .. } => *error,
+            error => error,
+        }
+    }
+
+    pub const fn exit_code(&self) -> u8 {
+        match self {
+            Self::CliError(_) | Self::FileNotInConfig(_) => EXIT_USAGE_ERROR,
+            Self::ConfigError { .. }
+            | Self::ThemeNotFound { .. }
+            | Self::ThemeValueError { .. }
+            | Self::ParentThemeNotFound { .. }
+            | Self::ThemeExtendsCycle(_)
+            | Self::ThemeValueCycle { .. }
+            | Self::ThemeFileError { .. }
+            | Self::DuplicateTheme { .. }
+            | Self::ThemeAlreadyExists(_)
+            | Self::ThemeFileExists(_)
+            | Self::SchemaMismatch { .. }
+            | Self::GlobError { .. }
+            | Self::InvalidThemes(_)
+            | Self::EnvVarNotFound(_)
+            | Self::UnsetPathVariable { .. } => EXIT_CONFIG_ERROR,
+            Self::TomlReadError(_)
+            | Self::TomlWriteError(_)
+            | Self::YamlReadError(_)
+            | Self::FileReadError(_)
+            | Self::WriteRolledBack { .. }
+            | Self::UndoRolledBack { .. }
+            | Self::BackupNotFound
+            | Self::BackupFileChanged { .. } => EXIT_IO_ERROR,
+            Self::RegexFail(_)
+            | Self::RegexMatchError { .. }
+            | Self::RegexNthMatchError { .. }
+            | Self::CaptureFail { .. }
+            | Self::FuncArgumentError { .. }
+            | Self::ParseError(_)
+            | Self::ArgumentTypeError { .. }
+            | Self::InvalidColour(_)
+            | Self::UnknownColourSpace(_)
+            | Self::UnknownColourFormat { .. }
+            | Self::UnknownHexStyle(_)
+            | Self::UnknownFunction { .. }
+            | Self::OverlappingEdits { .. }
+            | Self::ArgNotFound { .. }
+            | Self::Spanned { .. } => EXIT_TEMPLATE_ERROR,
+            Self::TemplateErrors { exit_code, .. } => *exit_code,
+        }
+    }
+
+    // A hint on how to fix the error, shown underneath it
+    pub fn help(&self) -> Option<String> {
+        match self {
+            Self::Spanned { error, .. }
+            | Self::ConfigError { error, .. }
+            | Self::ThemeValueError { error, .. } => error.help(),
+            Self::ArgNotFound {
+                suggestion: Some(suggestion),
+                ..
+            }
+            | Self::UnknownFunction {
+                suggestion: Some(suggestion),
+                ..
+            }
+            | Self::UnknownColourFormat {
+                suggestion: Some(suggestion),
+                ..
+            }
+            | Self::ParentThemeNotFound {
+                suggestion: Some(suggestion),
+                ..
+            } => Some(format!("did you mean `{suggestion}`?")),
+            Self::UnsetPathVariable { variable, .. } => Some(format!(
+                "set {variable}, or give it a default with ${{{variable}:-default}}"
+            )),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,333 @@
+use std::fmt::Display;
+
+use crate::colour::{Colour, ColourFormat, ColourSpace};
+use crate::diagnostics::closest_match;
+use crate::errors::DotfilesError;
+use crate::parser::{Argument, ArgumentKind, FunctionCall};
+
+// The names of every helper function which can be used as an argument
+pub const HELPER_NAMES: [&str; 12] = [
+    "complement",
+    "concat",
+    "darken",
+    "desaturate",
+    "format",
+    "invert",
+    "lighten",
+    "lower",
+    "mix",
+    "saturate",
+    "upper",
+    "with_alpha",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    // A hex colour, such as #A9B1D6
+    Colour(String),
+    Int(i64),
+    // A number with a decimal point, which is always written with one (So 1.0 stays as 1.0)
+    Float(f64),
+    Bool(bool),
+    List(Vec<Self>),
+}
+
+impl Value {
+    pub fn from_theme_value(value: String) -> Self {
+        // Theme values are all strings, so check whether they are colours
+        if Colour::from_hex(&value).is_ok() {
+            Self::Colour(value)
+        } else {
+            Self::String(value)
+        }
+    }
+
+    pub const fn kind_name(&self) -> &'static str {
+        match self {
+            Self::String(_) => "a string",
+            Self::Colour(_) => "a colour",
+            Self::Int(_) | Self::Float(_) => "a number",
+            Self::Bool(_) => "a boolean",
+            Self::List(_) => "a list",
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::String(value) | Self::Colour(value) => write!(f, "{value}"),
+            Self::Int(number) => write!(f, "{number}"),
+            Self::Float(number) if number.fract() == 0.0 => write!(f, "{number:.1}"),
+            Self::Float(number) => write!(f, "{number}"),
+            Self::Bool(boolean) => write!(f, "{boolean}"),
+            // Lists are written with a comma between each value, such as a list of fonts
+            Self::List(values) => write!(
+                f,
+                "{}",
+                values
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+impl From<Colour> for Value {
+    fn from(colour: Colour) -> Self {
+        Self::Colour(colour.to_hex())
+    }
+}
+
+// Evaluate an argument, looking up any variables it uses with the given function
+pub fn evaluate(
+    arg: &Argument,
+    variables: &dyn Fn(&str) -> Result<Value, DotfilesError>,
+) -> Result<Value, DotfilesError> {
+    let value = match &arg.kind {
+        ArgumentKind::Identifier(name) => variables(name)?,
+        ArgumentKind::Colour(colour) => {
+            Colour::from_hex(colour)?;
+            Value::Colour(colour.clone())
+        }
+        ArgumentKind::String(value) | ArgumentKind::Pattern(value) => Value::String(value.clone()),
+        ArgumentKind::Int(number) => Value::Int(*number),
+        ArgumentKind::Float(number) => Value::Float(*number),
+        ArgumentKind::Call(call) => call_helper(call, variables)?,
+    };
+
+    Ok(value)
+}
+
+struct HelperArgs<'a> {
+    call: &'a FunctionCall,
+    values: Vec<Value>,
+}
+
+fn call_helper(
+    call: &FunctionCall,
+    variables: &dyn Fn(&str) -> Result<Value, DotfilesError>,
+) -> Result<Value, DotfilesError> {
+    // Evaluate the arguments first, pointing at the argument which failed
+    let args = HelperArgs {
+        call,
+        values: call
+            .args
+            .iter()
+            .map(|arg| evaluate(arg, variables).map_err(|e| e.at(&arg.span)))
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+
+    let value = match call.name.as_str() {
+        // Requires: any number of values, which are joined together
+        "concat" => Value::String(args.values.iter().map(ToString::to_string).collect()),
+        // Requires: value
+        "lower" => {
+            args.expect_count(1, 1)?;
+            Value::String(args.values[0].to_string().to_lowercase())
+        }
+        // Requires: value
+        "upper" => {
+            args.expect_count(1, 1)?;
+            Value::String(args.values[0].to_string().to_uppercase())
+        }
+        // Requires: colour, amount (Optionally: colour space)
+        "lighten" | "darken" | "saturate" | "desaturate" => {
+            args.expect_count(2, 3)?;
+            let (colour, amount, space) =
+                (args.colour(0)?, args.fraction(1, -1.0)?, args.space(2)?);
+
+            match call.name.as_str() {
+                "lighten" => colour.lighten(amount, space),
+                "darken" => colour.lighten(-amount, space),
+                "saturate" => colour.saturate(amount, space),
+                _ => colour.saturate(-amount, space),
+            }
+            .into()
+        }
+        // Requires: colour, colour, ratio (Optionally: colour space)
+        "mix" => {
+            args.expect_count(3, 4)?;
+            args.colour(0)?
+                .mix(args.colour(1)?, args.fraction(2, 0.0)?, args.space(3)?)
+                .into()
+        }
+        // Requires: colour
+        "invert" => {
+            args.expect_count(1, 1)?;
+            args.colour(0)?.invert().into()
+        }
+        // Requires: colour (Optionally: colour space)
+        "complement" => {
+            args.expect_count(1, 2)?;
+            args.colour(0)?.complement(args.space(1)?).into()
+        }
+        // Requires: colour, alpha
+        "with_alpha" => {
+            args.expect_count(2, 2)?;
+            args.colour(0)?.with_alpha(args.fraction(1, 0.0)?).into()
+        }
+        // Requires: colour, format name
+        "format" => {
+            args.expect_count(2, 2)?;
+            Value::String(args.format(1)?.render(args.colour(0)?))
+        }
+        f => {
+            return Err(DotfilesError::UnknownFunction {
+                name: f.to_string(),
+                functions: HELPER_NAMES.iter().map(ToString::to_string).collect(),
+                suggestion: closest_match(f, HELPER_NAMES),
+            }
+            .at(&call.name_span));
+        }
+    };
+
+    Ok(value)
+}
+
+impl HelperArgs<'_> {
+    fn expect_count(&self, min: usize, max: usize) -> Result<(), DotfilesError> {
+        if (min..=max).contains(&self.values.len()) {
+            return Ok(());
+        }
+
+        Err(DotfilesError::FuncArgumentError {
+            name: self.call.name.clone(),
+            needed: if min == max {
+                min.to_string()
+            } else {
+                format!("{min} to {max}")
+            },
+            args: self.call.args.iter().map(|arg| arg.text.clone()).collect(),
+        })
+    }
+
+    fn type_error(&self, i: usize, expected: &str) -> DotfilesError {
+        DotfilesError::ArgumentTypeError {
+            name: self.call.name.clone(),
+            position: i + 1,
+            expected: expected.to_string(),
+            found: self.values[i].kind_name().to_string(),
+        }
+        .at(&self.call.args[i].span)
+    }
+
+    fn colour(&self, i: usize) -> Result<Colour, DotfilesError> {
+        match &self.values[i] {
+            Value::Colour(hex) => Colour::from_hex(hex).map_err(|e| e.at(&self.call.args[i].span)),
+            _ => Err(self.type_error(i, "a colour")),
+        }
+    }
+
+    fn number(&self, i: usize) -> Result<f64, DotfilesError> {
+        match &self.values[i] {
+            // Whole numbers are exact as floats, for any amount which makes sense for a colour
+            #[allow(clippy::cast_precision_loss)]
+            Value::Int(number) => Ok(*number as f64),
+            Value::Float(number) => Ok(*number),
+            _ => Err(self.type_error(i, "a number")),
+        }
+    }
+
+    fn fraction(&self, i: usize, min: f64) -> Result<f64, DotfilesError> {
+        // Amounts, ratios and alphas are fractions (So lighten(bg_col, 10) is an error, rather than being 1000% and giving white)
+        let number = self.number(i)?;
+        if (min..=1.0).contains(&number) {
+            return Ok(number);
+        }
+
+        Err(DotfilesError::ArgumentTypeError {
+            name: self.call.name.clone(),
+            position: i + 1,
+            expected: format!(
+                "a number from {}% to 100% (Such as 10% or 0.1)",
+                min * 100.0
+            ),
+            found: self.values[i].to_string(),
+        }
+        .at(&self.call.args[i].span))
+    }
+
+    fn format(&self, i: usize) -> Result<ColourFormat, DotfilesError> {
+        match &self.values[i] {
+            Value::String(name) => name
+                .parse()
+                .map_err(|e: DotfilesError| e.at(&self.call.args[i].span)),
+            _ => Err(self.type_error(i, "a colour format")),
+        }
+    }
+
+    fn space(&self, i: usize) -> Result<ColourSpace, DotfilesError> {
+        // The colour space is optional, using HSL by default
+        match self.values.get(i) {
+            None => Ok(ColourSpace::default()),
+            Some(Value::String(name)) => name
+                .parse()
+                .map_err(|e: DotfilesError| e.at(&self.call.args[i].span)),
+            Some(_) => Err(self.type_error(i, "a colour space (\"hsl\" or \"oklch\")")),
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_expression;
+
+    fn evaluate_text(text: &str) -> Result<Value, String> {
+        let variables = |name: &str| match name {
+            "bg" => Ok(Value::Colour("#000000".to_string())),
+            _ => Err(DotfilesError::ArgNotFound {
+                arg: name.to_string(),
+                theme: "t".to_string(),
+                suggestion: None,
+            }),
+        };
+
+        evaluate(
+            &parse_expression(text).expect("expression should parse"),
+            &variables,
+        )
+        .map_err(|e| e.unspanned().to_string())
+    }
+
+    fn colour(hex: &str) -> Value {
+        Value::Colour(hex.to_string())
+    }
+
+    #[test]
+    fn fractions() {
+        assert_eq!(evaluate_text("lighten(bg, 10%)"), Ok(colour("#1A1A1A")));
+        assert_eq!(evaluate_text("lighten(bg, 0.1)"), Ok(colour("#1A1A1A")));
+        assert_eq!(evaluate_text("darken(bg, -100%)"), Ok(colour("#FFFFFF")));
+        assert_eq!(
+            evaluate_text("with_alpha(bg, 50%)"),
+            Ok(colour("#00000080"))
+        );
+        assert_eq!(evaluate_text("mix(bg, #FFFFFF, 1)"), Ok(colour("#FFFFFF")));
+    }
+
+    #[test]
+    fn fractions_out_of_range() {
+        assert_eq!(
+            evaluate_text("lighten(bg, 10)"),
+            Err("Argument 2 of 'lighten' should be a number from -100% to 100% (Such as 10% or 0.1), found 10".to_string())
+        );
+        assert_eq!(
+            evaluate_text("saturate(bg, -150%)"),
+            Err("Argument 2 of 'saturate' should be a number from -100% to 100% (Such as 10% or 0.1), found -1.5".to_string())
+        );
+        assert_eq!(
+            evaluate_text("mix(bg, bg, -0.5)"),
+            Err("Argument 3 of 'mix' should be a number from 0% to 100% (Such as 10% or 0.1), found -0.5".to_string())
+        );
+        assert_eq!(
+            evaluate_text("with_alpha(bg, 80)"),
+            Err("Argument 2 of 'with_alpha' should be a number from 0% to 100% (Such as 10% or 0.1), found 80".to_string())
+        );
+    }
+}
@@ -1,14 +1,20 @@
+use std::collections::HashSet;
 use std::fmt::Debug;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::prelude::*;
 use std::ops::Range;
 use std::path::Path;
 
 use regex::Regex;
 
-use crate::config::{CONFIG, FileConfig, XDG_CONFIG_PATH};
+use crate::backup::{Snapshot, prune_snapshots};
+use crate::cli::{cli, info, verbose};
+use crate::config::{FileConfig, XDG_CONFIG_PATH, config};
+use crate::diagnostics::Diagnostics;
+use crate::diff::print_diff;
 use crate::errors::DotfilesError;
 use crate::functions::parse_and_run_function;
+use crate::schema::check_schema;
 
 #[derive(Debug, Clone)]
 pub struct MatchedText {
@@ -34,72 +40,661 @@ pub fn open_file<S: AsRef<str>>(path: S) -> Result<String, DotfilesError> {
     Ok(contents)
 }
 
-pub fn write_to_file<S: AsRef<str>>(
+pub fn write_to_file<S: AsRef<str>>(path: S, contents: &str) -> Result<(), DotfilesError> {
+    // Follow any symlinks so that the real file is replaced, rather than the link itself
+    let real_path = fs::canonicalize(path.as_ref())?;
+    let permissions = fs::metadata(&real_path)?.permissions();
+
+    // Write to a temporary file in the same directory, so that the rename cannot cross filesystems
+    let temp_path = {
+        let file_name = real_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        real_path.with_file_name(format!(
+            ".{file_name}.dotfile-templater.{}.tmp",
+            std::process::id()
+        ))
+    };
+
+    let write_result = (|| -> Result<(), DotfilesError> {
+        // Give the temporary file the original's permissions before anything is written to it (So a private file is never readable by others, even briefly)
+        let mut temp_file = File::create(&temp_path)?;
+        temp_file.set_permissions(permissions)?;
+        temp_file.write_all(contents.as_bytes())?;
+        temp_file.sync_all()?;
+
+        // Swap the temporary file into place (Atomic, so the file is never left half-written)
+        fs::rename(&temp_path, &real_path)?;
+
+        Ok(())
+    })();
+
+    // Don't leave the temporary file lying around if anything failed
+    if write_result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    write_result
+}
+
+pub fn apply_edits<S: AsRef<str>>(
     path: S,
-    replace_text: MatchedText,
-) -> Result<(), DotfilesError> {
-    // Copy the file before modifications
-    let mut config_file = File::open(path.as_ref())?;
-    let mut contents = String::new();
-    config_file.read_to_string(&mut contents)?;
+    contents: &str,
+    mut edits: Vec<MatchedText>,
+) -> Result<String, DotfilesError> {
+    // Sort the edits so they can be applied from the start of the file to the end
+    edits.sort_by_key(|edit| (edit.range.start, edit.range.end));
 
-    // Replace the region with the new text
-    contents.replace_range(replace_text.range, replace_text.text.as_str());
+    // Make sure that no two edits try to modify the same part of the file
+    for pair in edits.windows(2) {
+        if pair[1].range.start < pair[0].range.end {
+            return Err(DotfilesError::OverlappingEdits {
+                path: path.as_ref().to_string(),
+                first_range: pair[0].range.clone(),
+                first_text: contents[pair[0].range.clone()].to_string(),
+                second_range: pair[1].range.clone(),
+                second_text: contents[pair[1].range.clone()].to_string(),
+            });
+        }
+    }
 
-    // Write the changes to the file
-    let mut config_file = File::create(path.as_ref())?;
-    config_file.write_all(contents.as_bytes())?;
+    // Copy the unchanged text between each edit, and the replacement text for each edit (Ranges are with respect to the original contents, so lengths can change freely)
+    let mut new_contents = String::with_capacity(contents.len());
+    let mut last_end = 0;
+    for edit in edits {
+        new_contents.push_str(&contents[last_end..edit.range.start]);
+        new_contents.push_str(&edit.text);
+        last_end = edit.range.end;
+    }
+    new_contents.push_str(&contents[last_end..]);
 
-    Ok(())
+    Ok(new_contents)
 }
 
-pub fn modify_files() -> Result<(), DotfilesError> {
-    for file_config in &Into::<Vec<FileConfig>>::into(CONFIG.files.clone()) {
-        // Allow file_path to be absolute, or relative to .config
-        let path_str = {
-            let p = Path::new(file_config.file.as_str());
-            if p.is_absolute() {
-                p.display().to_string()
-            } else {
-                format!("{}/{}", *XDG_CONFIG_PATH, p.display())
-            }
-        };
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    // Write the changes to each file
+    Apply,
+    // Only report which files would change
+    Check,
+    // Print a unified diff of the changes which would be made to each file
+    Diff,
+}
 
-        println!("{path_str}");
+// Expand ~ (At the start), $VAR, ${VAR} and ${VAR:-default} in a path, giving an error if a variable isn't set (And has no default)
+pub fn expand_path(path: &str) -> Result<String, DotfilesError> {
+    expand_path_with(path, &|name| std::env::var(name).ok())
+}
 
-        let file = open_file(path_str.as_str())?;
+// Expand a path, looking up environment variables with the given function
+fn expand_path_with(
+    path: &str,
+    env_var: &dyn Fn(&str) -> Option<String>,
+) -> Result<String, DotfilesError> {
+    let unset = |variable: &str| DotfilesError::UnsetPathVariable {
+        path: path.to_string(),
+        variable: variable.to_string(),
+    };
 
-        // Find the parts which need to be replaced
-        let marker_regex_string = file_config
-            .marker_char
-            .to_string()
-            .repeat(CONFIG.marker_repetition_num);
-        let marker_regex = Regex::new(format!("(?m)(^.*){marker_regex_string}(.*)$").as_str())?;
+    let mut expanded = String::new();
+    let mut rest = path;
+    if rest == "~" || rest.starts_with("~/") {
+        expanded.push_str(&env_var("HOME").ok_or_else(|| unset("HOME"))?);
+        rest = &rest[1..];
+    }
 
-        // Find the lines which have the marker on them, and split the line into actual code and template code
-        for captures in marker_regex.captures_iter(&file) {
-            let Some(actual_text) = captures.get(1) else {
-                return Err(DotfilesError::CaptureFail {
-                    captures: format!("{captures:?}"),
-                    index: 1,
-                });
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        // ${VAR} or ${VAR:-default}, where the default is used if VAR isn't set or is empty (A $ without a closing } is kept as it is)
+        if let Some((variable, after)) = rest
+            .strip_prefix('{')
+            .and_then(|braced| braced.split_once('}'))
+        {
+            let (name, default) = match variable.split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (variable, None),
             };
+            let value = env_var(name).filter(|value| default.is_none() || !value.is_empty());
+
+            // The default can be a path itself, such as ${XDG_DATA_HOME:-~/.local/share}
+            expanded.push_str(&match (value, default) {
+                (Some(value), _) => value,
+                (None, Some(default)) => expand_path_with(default, env_var)?,
+                (None, None) => return Err(unset(name)),
+            });
+            rest = after;
+            continue;
+        }
+
+        // $VAR, which ends at the first character which can't be in a variable name (A $ on its own is kept as it is)
+        let length = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if length == 0 {
+            expanded.push('$');
+            continue;
+        }
+
+        let name = &rest[..length];
+        expanded.push_str(&env_var(name).ok_or_else(|| unset(name))?);
+        rest = &rest[length..];
+    }
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+pub fn resolve_path(path: &str) -> String {
+    // Allow path to be absolute, or relative to .config
+    let p = Path::new(path);
+    if p.is_absolute() {
+        p.display().to_string()
+    } else {
+        format!("{}/{}", *XDG_CONFIG_PATH, p.display())
+    }
+}
+
+fn is_file_selected(path_str: &str, file_config: &FileConfig) -> bool {
+    let selected_files = &cli().files;
+
+    // Every file is selected if no --file options were given
+    selected_files.is_empty()
+        || selected_files
+            .iter()
+            .any(|selected| selects_file(selected, path_str, file_config))
+}
+
+fn selects_file(selected: &str, path_str: &str, file_config: &FileConfig) -> bool {
+    // Only compare the real paths if both exist (Otherwise two missing files would be the same)
+    let same_real_path = matches!(
+        (fs::canonicalize(selected), fs::canonicalize(path_str)),
+        (Ok(selected_path), Ok(path)) if selected_path == path
+    );
+
+    selected == file_config.file || resolve_path(selected) == path_str || same_real_path
+}
+
+// A file to template, which is either in the config or was found with a glob or folder in the config
+struct TemplateFile<'a> {
+    path: String,
+    file_config: &'a FileConfig,
+    is_expanded: bool,
+}
+
+fn expand_files(files: &[FileConfig]) -> Result<Vec<TemplateFile<'_>>, DotfilesError> {
+    let mut seen_paths = HashSet::new();
+    let mut template_files = Vec::new();
+
+    for file_config in files {
+        let path_str = resolve_path(file_config.file.as_str());
 
-            let Some(template_text) = captures.get(2) else {
-                return Err(DotfilesError::CaptureFail {
-                    captures: format!("{captures:?}"),
-                    index: 2,
+        let (paths, is_expanded) = if Path::new(&path_str).is_dir() {
+            (files_in_folder(&path_str, file_config)?, true)
+        } else if file_config.file.contains(['*', '?', '[']) {
+            (glob_files(&path_str)?, true)
+        } else {
+            (vec![path_str], false)
+        };
+
+        // A file which more than one entry matches is only templated once, using the first entry
+        for path in paths {
+            let real_path = fs::canonicalize(&path).map_or_else(
+                |_| path.clone(),
+                |real_path| real_path.display().to_string(),
+            );
+            if seen_paths.insert(real_path) {
+                template_files.push(TemplateFile {
+                    path,
+                    file_config,
+                    is_expanded,
                 });
-            };
+            }
+        }
+    }
+
+    Ok(template_files)
+}
+
+fn glob_pattern(pattern: &str) -> Result<glob::Pattern, DotfilesError> {
+    glob::Pattern::new(pattern).map_err(|e| DotfilesError::GlobError {
+        pattern: pattern.to_string(),
+        error: e.to_string(),
+    })
+}
 
-            // Parse the template code, and modify the actual_text
-            parse_and_run_function(
-                path_str.as_str(),
-                &template_text.into(),
-                &actual_text.into(),
-            )?;
+fn glob_files(pattern: &str) -> Result<Vec<String>, DotfilesError> {
+    // Hidden files and folders (Such as .git) are only matched if the pattern asks for them, such as waybar/.*
+    let options = glob::MatchOptions {
+        require_literal_leading_dot: true,
+        ..glob::MatchOptions::new()
+    };
+    let paths = glob::glob_with(pattern, options).map_err(|e| DotfilesError::GlobError {
+        pattern: pattern.to_string(),
+        error: e.to_string(),
+    })?;
+
+    // Only keep the files, since a glob such as waybar/* can also match folders
+    let mut files = Vec::new();
+    for path in paths {
+        let path = path.map_err(std::io::Error::from)?;
+        if path.is_file() {
+            files.push(path.display().to_string());
         }
     }
 
+    if files.is_empty() {
+        verbose!("No files match '{pattern}'");
+    }
+
+    Ok(files)
+}
+
+fn files_in_folder(folder: &str, file_config: &FileConfig) -> Result<Vec<String>, DotfilesError> {
+    let to_patterns = |patterns: &[String]| {
+        patterns
+            .iter()
+            .map(|pattern| glob_pattern(pattern))
+            .collect::<Result<Vec<_>, _>>()
+    };
+    let (include, exclude) = (
+        to_patterns(&file_config.include)?,
+        to_patterns(&file_config.exclude)?,
+    );
+
+    // Every file within the folder (And the folders within it), filtered by the include and exclude patterns, which match the path within the folder
+    let all_files = glob_files(&format!("{}/**/*", glob::Pattern::escape(folder)))?;
+    Ok(all_files
+        .into_iter()
+        .filter(|path| {
+            let relative_path = Path::new(path)
+                .strip_prefix(folder)
+                .unwrap_or_else(|_| Path::new(path));
+
+            (include.is_empty()
+                || include
+                    .iter()
+                    .any(|pattern| pattern.matches_path(relative_path)))
+                && !exclude
+                    .iter()
+                    .any(|pattern| pattern.matches_path(relative_path))
+        })
+        .collect())
+}
+
+#[derive(Debug, Clone)]
+pub struct PendingFile {
+    pub path: String,
+    pub old_contents: String,
+    pub new_contents: String,
+}
+
+pub fn modify_files(mode: Mode) -> Result<Vec<String>, DotfilesError> {
+    modify_files_with(mode, Vec::new())
+}
+
+// Modify the template files, along with other files which should be written (And backed up) with them, so long as every template was fine
+pub fn modify_files_with(
+    mode: Mode,
+    extra_files: Vec<PendingFile>,
+) -> Result<Vec<String>, DotfilesError> {
+    let config = config()?;
+
+    // Make sure the theme exists, rather than every template failing to find it
+    let themes = config.get_theme_hashmap();
+    let Some(theme) = themes.get(&config.theme) else {
+        return Err(DotfilesError::ThemeNotFound {
+            name: config.theme.clone(),
+            themes: config.get_theme_names(),
+        });
+    };
+
+    // Make sure the theme (And each file's own theme) has every variable in the schema, before any file is modified
+    check_schema(theme, &config.schema)?;
+    for file_theme in config
+        .files
+        .iter()
+        .filter_map(|file| file.file_theme.as_ref())
+    {
+        check_schema(file_theme, &config.schema)?;
+    }
+
+    // Keep going after an error, so every problem can be reported at once
+    let mut diagnostics = Diagnostics::default();
+
+    // Work out the new contents of every file before anything is written
+    let mut pending_files = Vec::new();
+    let template_files = expand_files(&config.files)?;
+
+    // Make sure every file given with --file is in the config, rather than silently doing nothing
+    if let Some(unknown_file) = cli().files.iter().find(|selected| {
+        !template_files
+            .iter()
+            .any(|file| selects_file(selected, &file.path, file.file_config))
+    }) {
+        return Err(DotfilesError::FileNotInConfig(unknown_file.clone()));
+    }
+
+    for TemplateFile {
+        path: path_str,
+        file_config,
+        is_expanded,
+    } in template_files
+    {
+        // Skip files which weren't selected with --file
+        if !is_file_selected(path_str.as_str(), file_config) {
+            continue;
+        }
+
+        let file = match open_file(path_str.as_str()) {
+            Ok(file) => file,
+            // Files from a glob or folder which can't be read as text (Such as images) are skipped quietly
+            Err(e) if is_expanded => {
+                verbose!("Skipping {path_str}: {e}");
+                continue;
+            }
+            Err(e) => {
+                info!("{path_str}");
+                diagnostics.set_file(path_str.as_str(), "");
+                diagnostics.error(e);
+                continue;
+            }
+        };
+
+        // Files from a glob or folder which don't have any templates are skipped quietly
+        if is_expanded && marker_regex(file_config).is_ok_and(|regex| !regex.is_match(&file)) {
+            continue;
+        }
+
+        info!("{path_str}");
+        diagnostics.set_file(path_str.as_str(), &file);
+
+        // Only keep the files which have changed
+        match template_file(path_str.as_str(), &file, file_config, &mut diagnostics) {
+            Ok(Some(new_contents)) => pending_files.push(PendingFile {
+                path: path_str,
+                old_contents: file,
+                new_contents,
+            }),
+            Ok(None) => {}
+            Err(e) => diagnostics.error(e),
+        }
+    }
+
+    if diagnostics.is_empty() {
+        pending_files.extend(extra_files);
+    }
+
+    // With --strict, nothing is written unless every template in every file was fine
+    let skip_writing = cli().strict && !diagnostics.is_empty();
+
+    match mode {
+        Mode::Apply if skip_writing => {
+            eprintln!("Not modifying any files, since errors were found (--strict)");
+        }
+        Mode::Apply => write_all_files(&pending_files)?,
+        Mode::Check => {}
+        Mode::Diff => {
+            for pending in &pending_files {
+                print_diff(&pending.path, &pending.old_contents, &pending.new_contents);
+            }
+        }
+    }
+
+    // Report every error which was found
+    diagnostics.into_result()?;
+
+    // Return which files have been (or would be) changed
+    Ok(pending_files
+        .into_iter()
+        .map(|pending| pending.path)
+        .collect())
+}
+
+fn marker_regex(file_config: &FileConfig) -> Result<Regex, DotfilesError> {
+    let marker_regex_string = file_config
+        .marker_char
+        .repeat(config()?.marker_repetition_num);
+
+    Ok(Regex::new(
+        format!("(?m)(^.*){marker_regex_string}(.*)$").as_str(),
+    )?)
+}
+
+fn template_file(
+    path_str: &str,
+    file: &str,
+    file_config: &FileConfig,
+    diagnostics: &mut Diagnostics,
+) -> Result<Option<String>, DotfilesError> {
+    // Find the parts which need to be replaced
+    let marker_regex = marker_regex(file_config)?;
+
+    // Find the lines which have the marker on them, and split the line into actual code and template code
+    let mut edits = Vec::new();
+    for captures in marker_regex.captures_iter(file) {
+        let (Some(actual_text), Some(template_text)) = (captures.get(1), captures.get(2)) else {
+            diagnostics.error(DotfilesError::CaptureFail {
+                captures: format!("{captures:?}"),
+                index: 2,
+            });
+            continue;
+        };
+
+        // Parse the template code, and collect the modifications to the actual_text (Lines with errors are left as they are)
+        let error_count = diagnostics.len();
+        let line_edits = parse_and_run_function(
+            &template_text.into(),
+            &actual_text.into(),
+            file_config,
+            diagnostics,
+        );
+        if diagnostics.len() == error_count {
+            edits.extend(line_edits);
+        }
+    }
+
+    // Rebuild the file from all of its edits at once
+    if edits.is_empty() {
+        Ok(None)
+    } else {
+        apply_edits(path_str, file, edits).map(Some)
+    }
+}
+
+fn write_all_files(pending_files: &[PendingFile]) -> Result<(), DotfilesError> {
+    if pending_files.is_empty() {
+        return Ok(());
+    }
+
+    // Back up the files before they are modified (Unless backups are turned off)
+    let mut snapshot = if config()?.backup_count > 0 {
+        Some(Snapshot::create()?)
+    } else {
+        None
+    };
+
+    for (i, pending) in pending_files.iter().enumerate() {
+        let write_result = snapshot
+            .as_mut()
+            .map_or(Ok(()), |snapshot| {
+                snapshot.add_file(&pending.path, &pending.old_contents, &pending.new_contents)
+            })
+            .and_then(|()| write_to_file(&pending.path, &pending.new_contents));
+
+        // Put back the files which were already written, so they are all left on the same theme
+        if let Err(e) = write_result {
+            let not_restored = pending_files[..i]
+                .iter()
+                .filter(|written| write_to_file(&written.path, &written.old_contents).is_err())
+                .map(|written| written.path.clone())
+                .collect::<Vec<_>>();
+
+            // The files are back to how they were, so there is nothing to undo (Otherwise only the files which couldn't be restored are kept, since the others no longer match the written contents)
+            if let Some(mut snapshot) = snapshot {
+                if not_restored.is_empty() {
+                    let _ = fs::remove_dir_all(snapshot.dir);
+                } else {
+                    let _ = snapshot.retain_files(&not_restored);
+                }
+            }
+
+            return Err(DotfilesError::WriteRolledBack {
+                path: pending.path.clone(),
+                error: Box::new(e),
+                restored: i - not_restored.len(),
+                not_restored,
+            });
+        }
+    }
+
+    // Remove any backups which are too old to keep
+    if snapshot.is_some() {
+        prune_snapshots()?;
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    fn expand(path: &str) -> Result<String, String> {
+        let env_var = |name: &str| match name {
+            "HOME" => Some("/home/me".to_string()),
+            "XDG_DATA_HOME" => Some("/data".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        };
+
+        expand_path_with(path, &env_var).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn tilde() {
+        assert_eq!(expand("~"), Ok("/home/me".to_string()));
+        assert_eq!(
+            expand("~/.Xresources"),
+            Ok("/home/me/.Xresources".to_string())
+        );
+        // Only a ~ at the start, on its own, is the home folder
+        assert_eq!(expand("~user/x"), Ok("~user/x".to_string()));
+        assert_eq!(expand("a/~/b"), Ok("a/~/b".to_string()));
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(expand("$HOME/.bashrc"), Ok("/home/me/.bashrc".to_string()));
+        assert_eq!(
+            expand("${XDG_DATA_HOME}/rofi"),
+            Ok("/data/rofi".to_string())
+        );
+        assert_eq!(
+            expand("$HOME-$XDG_DATA_HOME"),
+            Ok("/home/me-/data".to_string())
+        );
+        assert_eq!(expand("a${EMPTY}b"), Ok("ab".to_string()));
+        assert_eq!(expand("a${EMPTY}b$EMPTY"), Ok("ab".to_string()));
+    }
+
+    #[test]
+    fn defaults() {
+        assert_eq!(
+            expand("${XDG_DATA_HOME:-/x}/rofi"),
+            Ok("/data/rofi".to_string())
+        );
+        assert_eq!(expand("${UNSET:-/x}/rofi"), Ok("/x/rofi".to_string()));
+        assert_eq!(expand("${UNSET:-~/x}"), Ok("/home/me/x".to_string()));
+        // A variable which is set but empty uses the default
+        assert_eq!(expand("${EMPTY:-/x}"), Ok("/x".to_string()));
+        assert_eq!(expand("${UNSET:-}a"), Ok("a".to_string()));
+    }
+
+    #[test]
+    fn dollars_which_are_not_variables() {
+        assert_eq!(expand("cost$"), Ok("cost$".to_string()));
+        assert_eq!(expand("a$/b"), Ok("a$/b".to_string()));
+        assert_eq!(expand("${HOME"), Ok("${HOME".to_string()));
+    }
+
+    #[test]
+    fn unset_variables() {
+        let unset = |variable: &str, path: &str| {
+            Err(DotfilesError::UnsetPathVariable {
+                path: path.to_string(),
+                variable: variable.to_string(),
+            }
+            .to_string())
+        };
+
+        assert_eq!(expand("$UNSET/x"), unset("UNSET", "$UNSET/x"));
+        assert_eq!(expand("${UNSET}/x"), unset("UNSET", "${UNSET}/x"));
+    }
+
+    fn edit(range: Range<usize>, text: &str) -> MatchedText {
+        MatchedText {
+            range,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn edits_of_different_lengths() {
+        let contents = "bg = #fff\nfg = #000000\n";
+
+        // A shorter and a longer replacement, so the later edit would be in the wrong place if positions were shifted
+        let edits = vec![edit(5..9, "#1a1b26"), edit(15..22, "#fff")];
+
+        assert_eq!(
+            apply_edits("f", contents, edits).expect("edits should apply"),
+            "bg = #1a1b26\nfg = #fff\n"
+        );
+    }
+
+    #[test]
+    fn edits_in_any_order() {
+        let contents = "a b c";
+        let edits = vec![edit(4..5, "z"), edit(0..1, "xx"), edit(2..3, "")];
+
+        assert_eq!(
+            apply_edits("f", contents, edits).expect("edits should apply"),
+            "xx  z"
+        );
+    }
+
+    #[test]
+    fn touching_edits_and_no_edits() {
+        assert_eq!(
+            apply_edits("f", "abcd", vec![edit(0..2, "x"), edit(2..4, "y")])
+                .expect("edits should apply"),
+            "xy"
+        );
+        assert_eq!(
+            apply_edits("f", "abcd", Vec::new()).expect("edits should apply"),
+            "abcd"
+        );
+    }
+
+    #[test]
+    fn overlapping_edits() {
+        let Err(DotfilesError::OverlappingEdits {
+            path,
+            first_range,
+            first_text,
+            second_range,
+            second_text,
+        }) = apply_edits("f", "abcdef", vec![edit(2..5, "x"), edit(0..3, "y")])
+        else {
+            panic!("expected overlapping edits to be an error");
+        };
+
+        assert_eq!(path, "f");
+        assert_eq!((first_range, first_text.as_str()), (0..3, "abc"));
+        assert_eq!((second_range, second_text.as_str()), (2..5, "cde"));
+    }
+}
@@ -1,208 +1,488 @@
 use regex::Regex;
 
 use crate::arguments::parse_argument;
+use crate::cli::{info, verbose};
+use crate::colour::{Colour, ColourFormat, HexStyle, match_hex_style};
+use crate::config::FileConfig;
+use crate::diagnostics::{Diagnostics, closest_match};
 use crate::errors::DotfilesError;
-use crate::file::{MatchedText, write_to_file};
-use crate::regex::{
-    FUNCTION_REGEX, HEX_COLOUR_REGEX, get_nth_match, get_single_match, matches_keyword_or_string,
-    matches_pattern,
-};
+use crate::expression::{Value, evaluate};
+use crate::file::MatchedText;
+use crate::parser::{Argument, ArgumentKind, FunctionCall, parse_template};
+use crate::regex::{HEX_COLOUR_REGEX, get_nth_match, get_single_match};
+
+// The names of every function which can be used in a template
+pub const FUNCTION_NAMES: [&str; 7] = [
+    "replace",
+    "replace-bool",
+    "replace-col",
+    "replace-col-as",
+    "replace-num",
+    "replace-pattern",
+    "replace-pattern-col",
+];
+
+// The patterns matched by replace-num and replace-bool
+const NUMBER_PATTERN: &str = r"-?\b\d+(?:\.\d+)?";
+const BOOL_PATTERN: &str = r"\b(?:true|false)\b";
 
 pub fn parse_and_run_function(
-    file_path: &str,
     function_code_text: &MatchedText,
     actual_text: &MatchedText,
-) -> Result<(), DotfilesError> {
-    // Match the first function pattern // TODO Attempt to allow multiple functions on the same line
-    let functions = FUNCTION_REGEX
-        .clone()?
-        .captures_iter(&function_code_text.text)
-        .map(|function_captures| {
-            // Extract the groups
-            let (_, [name, args]) = function_captures.extract();
-
-            // Remove the brackets around the arguments, then split them based on commas
-            let args = args
-                .trim_start_matches('(')
-                .trim_end_matches(')')
-                .split(',')
-                .map(str::trim) // Make sure the remove excess whitespace on the arguments
-                .collect::<Vec<&str>>();
-
-            // Print the function and its arguments (This will help to track what is happening)
-            println!("\t{name}({})", args.join(", "));
-
-            (name.to_string(), args)
-        })
-        .collect::<Vec<_>>();
+    file_config: &FileConfig,
+    diagnostics: &mut Diagnostics,
+) -> Vec<MatchedText> {
+    // Parse every function call in the template code
+    let calls = match parse_template(function_code_text) {
+        Ok(calls) => calls,
+        Err(e) => {
+            diagnostics.error_at(function_code_text.range.clone(), e);
+            return Vec::new();
+        }
+    };
+
+    // Run each function on the specified text, collecting the edits which they produce (Errors are recorded, and the other functions still run)
+    let mut edits = Vec::new();
+    for (i, call) in calls.iter().enumerate() {
+        // Print the function and its arguments (This will help to track what is happening)
+        verbose!("\t{call}");
+
+        match run_function(call, actual_text, i, file_config) {
+            Ok(Some(edit)) => edits.push(edit),
+            Ok(None) => {}
+            Err(e) => diagnostics.error_at(call.span.clone(), e),
+        }
+    }
+
+    edits
+}
+
+fn check_args(call: &FunctionCall, kinds: &[ArgumentSlot]) -> Result<(), DotfilesError> {
+    let required = kinds
+        .iter()
+        .filter(|slot| !matches!(slot, ArgumentSlot::OptionalValue))
+        .count();
 
-    // Run each function on the specified file
-    for (i, (name, args)) in functions.iter().enumerate() {
-        run_function(name, args, file_path.to_string(), actual_text, i)?;
+    if !(required..=kinds.len()).contains(&call.args.len()) {
+        // Incorrect number of arguments
+        return Err(DotfilesError::FuncArgumentError {
+            name: call.name.clone(),
+            needed: if required == kinds.len() {
+                required.to_string()
+            } else {
+                format!("{required} to {}", kinds.len())
+            },
+            args: call.args.iter().map(|arg| arg.text.clone()).collect(),
+        });
+    }
+
+    // Make sure each argument is of the right kind
+    for (i, (arg, slot)) in call.args.iter().zip(kinds).enumerate() {
+        let is_valid = match slot {
+            ArgumentSlot::Pattern => matches!(arg.kind, ArgumentKind::Pattern(_)),
+            ArgumentSlot::Value | ArgumentSlot::OptionalValue => {
+                !matches!(arg.kind, ArgumentKind::Pattern(_))
+            }
+        };
+
+        if !is_valid {
+            return Err(DotfilesError::ArgumentTypeError {
+                name: call.name.clone(),
+                position: i + 1,
+                expected: slot.description().to_string(),
+                found: arg.kind.kind_name().to_string(),
+            }
+            .at(&arg.span));
+        }
     }
 
     Ok(())
 }
 
+#[derive(Debug, Clone, Copy)]
+enum ArgumentSlot {
+    // A 'pattern' to match
+    Pattern,
+    // A theme variable, "string", number, colour or helper function to replace with
+    Value,
+    // A value which can be left out (Only at the end of the arguments)
+    OptionalValue,
+}
+
+impl ArgumentSlot {
+    const fn description(self) -> &'static str {
+        match self {
+            Self::Pattern => "a pattern",
+            Self::Value | Self::OptionalValue => {
+                "a theme variable, string, number, colour or helper function"
+            }
+        }
+    }
+}
+
 pub fn run_function(
-    name: &str,
-    args: &[&str],
-    file_path: String,
+    call: &FunctionCall,
     text: &MatchedText,
     index_to_match: usize,
-) -> Result<(), DotfilesError> {
-    match name {
+    file_config: &FileConfig,
+) -> Result<Option<MatchedText>, DotfilesError> {
+    // The replace-col functions can be given a hex style, otherwise the file's style is used
+    let hex_style = |arg: Option<&Argument>| -> Result<HexStyle, DotfilesError> {
+        arg.map_or(Ok(file_config.hex_style), |arg| {
+            evaluate_argument(arg, file_config)?
+                .to_string()
+                .parse()
+                .map_err(|e: DotfilesError| e.at(&arg.span))
+        })
+    };
+
+    // The functions which match colours use the colour pattern, pointing at the whole function call if it doesn't match
+    let call_pattern = |pattern: &str| Argument {
+        kind: ArgumentKind::Pattern(pattern.to_string()),
+        text: pattern.to_string(),
+        span: call.span.clone(),
+    };
+    let colour_pattern = || -> Result<Argument, DotfilesError> {
+        Ok(call_pattern(HEX_COLOUR_REGEX.clone()?.as_str()))
+    };
+
+    let args = &call.args;
+    let edit = match call.name.as_str() {
         // Requires: pattern, replace-string
         "replace" => {
-            if args.len() == 2 {
-                matches_pattern(args[0])?; // First argument is a pattern
-                matches_keyword_or_string(args[1])?; // Second argument is a keyword or string
+            check_args(call, &[ArgumentSlot::Pattern, ArgumentSlot::Value])?;
 
-                // Run the function
-                replace_fn(file_path, args, text, index_to_match)?;
-            } else {
-                // Incorrect number of arguments
-                return Err(DotfilesError::FuncArgumentError {
-                    name: name.to_string(),
-                    needed: 2,
-                    args: args.iter().map(ToString::to_string).collect(),
-                });
-            }
+            // Run the function
+            replace_fn(args, text, index_to_match, None, file_config)?
         }
-        // R
+        // Requires: replace-string (The pattern is a colour) (Optionally: hex style)
         "replace-col" => {
-            if args.len() == 1 {
-                matches_keyword_or_string(args[0])?; // First argument is a keyword or string
-
-                // Run the function
-                replace_fn(
-                    file_path,
-                    &[HEX_COLOUR_REGEX.clone()?.as_str(), args[0]],
-                    text,
-                    index_to_match,
-                )?;
+            check_args(call, &[ArgumentSlot::Value, ArgumentSlot::OptionalValue])?;
+
+            // Run the function
+            replace_fn(
+                &[colour_pattern()?, args[0].clone()],
+                text,
+                index_to_match,
+                Some(hex_style(args.get(1))?),
+                file_config,
+            )?
+        }
+        // Requires: format name, colour (The pattern is a colour written in that format)
+        "replace-col-as" => {
+            check_args(call, &[ArgumentSlot::Value, ArgumentSlot::Value])?;
+
+            // Write the colour in the format, then replace a colour which is in that same format
+            let (format, replacement) =
+                formatted_colour_argument(call, text, index_to_match, file_config)?;
+
+            // Run the function
+            replace_fn(
+                &[call_pattern(format.pattern()), replacement],
+                text,
+                index_to_match,
+                None,
+                file_config,
+            )?
+        }
+        // Requires: number or boolean (The pattern is a number such as 0.95, or true or false)
+        "replace-num" | "replace-bool" => {
+            check_args(call, &[ArgumentSlot::Value])?;
+            let (pattern, kind) = if call.name == "replace-num" {
+                (NUMBER_PATTERN, "a number")
             } else {
-                // Incorrect number of arguments
-                return Err(DotfilesError::FuncArgumentError {
-                    name: name.to_string(),
-                    needed: 1,
-                    args: args.iter().map(ToString::to_string).collect(),
-                });
-            }
+                (BOOL_PATTERN, "a boolean")
+            };
+            evaluate_argument_of_kind(call, 0, kind, file_config)?;
+
+            // Run the function
+            replace_fn(
+                &[call_pattern(pattern), args[0].clone()],
+                text,
+                index_to_match,
+                None,
+                file_config,
+            )?
         }
         // Replace function which also puts a pattern onto the text which is going to replace, and applies that same pattern to the text_to_replace (so they're the same length)
         "replace-pattern" => {
-            if args.len() == 3 {
-                matches_pattern(args[0])?; // First argument is a pattern
-                matches_keyword_or_string(args[1])?; // Second argument is a keyword or string
-                matches_pattern(args[2])?; // Third argument is a pattern
+            check_args(
+                call,
+                &[
+                    ArgumentSlot::Pattern,
+                    ArgumentSlot::Value,
+                    ArgumentSlot::Pattern,
+                ],
+            )?;
 
-                // Run the function
-                replace_fn(file_path, args, text, index_to_match)?;
-            } else {
-                // Incorrect number of arguments
-                return Err(DotfilesError::FuncArgumentError {
-                    name: name.to_string(),
-                    needed: 3,
-                    args: args.iter().map(ToString::to_string).collect(),
-                });
-            }
+            // Run the function
+            replace_fn(args, text, index_to_match, None, file_config)?
         }
         // Replace function which also puts a pattern onto the text which is going to replace, and applies that same pattern to the text_to_replace (so they're the same length), Also the initial pattern to match is the colour pattern
         "replace-pattern-col" => {
-            if args.len() == 2 {
-                matches_keyword_or_string(args[0])?; // First argument is a keyword or string
-                matches_pattern(args[1])?; // Second argument is a pattern
-
-                // Run the function
-                replace_fn(
-                    file_path,
-                    &[HEX_COLOUR_REGEX.clone()?.as_str(), args[0], args[1]],
-                    text,
-                    index_to_match,
-                )?;
-            } else {
-                // Incorrect number of arguments
-                return Err(DotfilesError::FuncArgumentError {
-                    name: name.to_string(),
-                    needed: 2,
-                    args: args.iter().map(ToString::to_string).collect(),
-                });
-            }
+            check_args(
+                call,
+                &[
+                    ArgumentSlot::Value,
+                    ArgumentSlot::Pattern,
+                    ArgumentSlot::OptionalValue,
+                ],
+            )?;
+
+            // Run the function
+            replace_fn(
+                &[colour_pattern()?, args[0].clone(), args[1].clone()],
+                text,
+                index_to_match,
+                Some(hex_style(args.get(2))?),
+                file_config,
+            )?
         }
         f => {
-            eprintln!("Function '{f}' does not exist");
+            return Err(DotfilesError::UnknownFunction {
+                name: f.to_string(),
+                functions: FUNCTION_NAMES.iter().map(ToString::to_string).collect(),
+                suggestion: closest_match(f, FUNCTION_NAMES),
+            }
+            .at(&call.name_span));
         }
-    }
+    };
 
-    Ok(())
+    Ok(edit)
 }
 
 // -------------------------------------------------------------------------------------------------------------------------------
 // ---------------------------------- Code to perform each function call on the specified file -----------------------------------
 // -------------------------------------------------------------------------------------------------------------------------------
 
+fn pattern_regex(arg: &Argument) -> Result<Regex, DotfilesError> {
+    // Turn a pattern argument into a Regex, pointing at the argument if it isn't valid
+    let ArgumentKind::Pattern(pattern) = &arg.kind else {
+        return Err(DotfilesError::ParseError(format!(
+            "Expected a pattern, found {}",
+            arg.kind.kind_name()
+        ))
+        .at(&arg.span));
+    };
+
+    Regex::new(pattern).map_err(|e| DotfilesError::from(e).at(&arg.span))
+}
+
+fn evaluate_argument(arg: &Argument, file_config: &FileConfig) -> Result<Value, DotfilesError> {
+    // Evaluate an argument using the file's theme, pointing at the argument if it fails
+    evaluate(arg, &|name| parse_argument(name, file_config)).map_err(|e| e.at(&arg.span))
+}
+
+fn formatted_colour_argument(
+    call: &FunctionCall,
+    text: &MatchedText,
+    index_to_match: usize,
+    file_config: &FileConfig,
+) -> Result<(ColourFormat, Argument), DotfilesError> {
+    let args = &call.args;
+    let format = evaluate_argument(&args[0], file_config)?
+        .to_string()
+        .parse::<ColourFormat>()
+        .map_err(|e| e.at(&args[0].span))?;
+    let hex = evaluate_argument_of_kind(call, 1, "a colour", file_config)?;
+    let colour = Colour::from_hex(&hex.to_string()).map_err(|e| e.at(&args[1].span))?;
+
+    // The colour written in the format is the argument which replaces the old colour (Keeping the old colour's alpha, like replace-col)
+    let old_text = Regex::new(format.pattern())
+        .ok()
+        .and_then(|regex| get_nth_match(&regex, text.clone(), index_to_match).ok());
+    let formatted = format.render_replacing(colour, old_text.as_ref().map(|old| old.text.as_str()));
+    let replacement = Argument {
+        kind: ArgumentKind::String(formatted.clone()),
+        text: formatted,
+        span: args[1].span.clone(),
+    };
+
+    Ok((format, replacement))
+}
+
+fn evaluate_argument_of_kind(
+    call: &FunctionCall,
+    i: usize,
+    kind: &str,
+    file_config: &FileConfig,
+) -> Result<Value, DotfilesError> {
+    // Evaluate an argument which has to be a certain kind of value, such as a number for replace-num
+    let value = evaluate_argument(&call.args[i], file_config)?;
+    if value.kind_name() != kind {
+        return Err(DotfilesError::ArgumentTypeError {
+            name: call.name.clone(),
+            position: i + 1,
+            expected: kind.to_string(),
+            found: value.kind_name().to_string(),
+        }
+        .at(&call.args[i].span));
+    }
+
+    Ok(value)
+}
+
+fn colour_replacement(old_text: &str, new_text: &str) -> Result<String, DotfilesError> {
+    let old_colour = Colour::from_hex(old_text)?;
+    Colour::from_hex(new_text)?;
+
+    // Keep the alpha of the old colour if the new colour doesn't have its own (Matching the case of the new colour, and writing #RGB as #RRGGBB so the alpha fits)
+    if matches!(new_text.len(), 4 | 7) && !old_colour.is_opaque() {
+        let alpha = if new_text.chars().any(|c| c.is_ascii_lowercase()) {
+            old_colour.alpha_hex().to_lowercase()
+        } else {
+            old_colour.alpha_hex()
+        };
+        let digits = if new_text.len() == 4 {
+            new_text[1..].chars().flat_map(|c| [c, c]).collect()
+        } else {
+            new_text[1..].to_string()
+        };
+
+        return Ok(format!("#{digits}{alpha}"));
+    }
+
+    Ok(new_text.to_string())
+}
+
 fn replace_fn(
-    file_path: String,
-    args: &[&str],
+    args: &[Argument],
     text: &MatchedText,
     index_to_match: usize,
-) -> Result<(), DotfilesError> {
-    // Parse the 2nd argument, to convert keywords into strings
-    let mut keyword_as_string = parse_argument(args[1].trim_matches('\"'))?;
+    hex_style: Option<HexStyle>,
+    file_config: &FileConfig,
+) -> Result<Option<MatchedText>, DotfilesError> {
+    let (pattern, keyword) = (&args[0], &args[1]);
+
+    // Evaluate the 2nd argument, to convert keywords and helper functions into strings
+    let mut keyword_as_string = evaluate_argument(keyword, file_config)?.to_string();
 
-    // Remove the surrounding apostrophes from the pattern, then turn it into a Regex
-    let replace_pattern_regex = Regex::new(args[0].trim_matches('\''))?;
+    // Turn the pattern into a Regex
+    let replace_pattern_regex = pattern_regex(pattern)?;
 
     // Match the text with this pattern (Choosing the nth match)
-    let mut text_to_replace = get_nth_match(&replace_pattern_regex, text.clone(), index_to_match)?;
+    let mut text_to_replace = get_nth_match(&replace_pattern_regex, text.clone(), index_to_match)
+        .map_err(|e| e.at(&pattern.span))?;
 
-    // Check if there is a pattern to apply to text_to_replace and the keyword_as_string
-    if let Some(&pattern_str_2) = args.get(2) {
-        // Check that this third argument is a pattern
-        matches_pattern(pattern_str_2)?;
+    // The replace-col functions check that the new text is a colour, and deal with the alpha and style of the old colour
+    if let Some(hex_style) = hex_style {
+        keyword_as_string = colour_replacement(&text_to_replace.text, &keyword_as_string)
+            .map_err(|e| e.at(&keyword.span))?;
 
-        // Remove the surrounding apostrophes from the pattern, and turn it into a Regex
-        let keyword_pattern_regex = Regex::new(pattern_str_2.trim_matches('\''))?;
+        if hex_style == HexStyle::Preserve {
+            keyword_as_string = match_hex_style(&keyword_as_string, &text_to_replace.text);
+        }
+    }
+
+    // Colours are compared ignoring case (So #FFFFFF is not replaced with #ffffff)
+    let is_same_colour = hex_style.is_some()
+        && args.len() == 2
+        && Colour::from_hex(&text_to_replace.text)? == Colour::from_hex(&keyword_as_string)?;
+
+    // Check if there is a pattern to apply to text_to_replace and the keyword_as_string
+    if let Some(keyword_pattern) = args.get(2) {
+        // Turn the third argument into a Regex
+        let keyword_pattern_regex = pattern_regex(keyword_pattern)?;
 
         // Shrink the text_to_replace to fit the new pattern
-        text_to_replace = get_single_match(&keyword_pattern_regex, text_to_replace.clone())?;
+        text_to_replace = get_single_match(&keyword_pattern_regex, text_to_replace.clone())
+            .map_err(|e| e.at(&keyword_pattern.span))?;
 
         // Perform the pattern matching on the keyword as well, giving a dummy range so the function signature is correct
         let keyword_matched_text = MatchedText {
             range: 0..1, // Not used
             text: keyword_as_string,
         };
-        keyword_as_string = get_single_match(&keyword_pattern_regex, keyword_matched_text)?.text;
-    }
-
-    // TODO This is so that the file length and locations don't change (Should fix this issue at some point)
-    if text_to_replace.range.len() != keyword_as_string.len() {
-        return Err(DotfilesError::ReplaceTextDifferentLength {
-            text_to_replace: text_to_replace.text,
-            replace_text: keyword_as_string,
-        });
+        keyword_as_string = get_single_match(&keyword_pattern_regex, keyword_matched_text)
+            .map_err(|e| e.at(&keyword_pattern.span))?
+            .text;
     }
 
     // Only replace if the text has changed
-    if text_to_replace.text != keyword_as_string {
-        println!(
+    let edit = if is_same_colour || text_to_replace.text == keyword_as_string {
+        None
+    } else {
+        info!(
             "\t\t'{}'\n\t\t{}  -->  {}",
             text.text.trim(),
             text_to_replace.text,
             keyword_as_string
         );
+        info!();
 
-        // Replace the text in the file
-        write_to_file(
-            file_path,
-            MatchedText {
-                range: text_to_replace.range,
-                text: keyword_as_string,
-            },
-        )?;
+        // Return the edit (The range is with respect to the original file, and it is applied once every edit is known)
+        Some(MatchedText {
+            range: text_to_replace.range,
+            text: keyword_as_string,
+        })
+    };
+
+    Ok(edit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replacement(old_text: &str, new_text: &str) -> Result<String, String> {
+        colour_replacement(old_text, new_text).map_err(|e| e.to_string())
     }
-    println!();
 
-    Ok(())
+    #[test]
+    fn keeps_the_old_alpha() {
+        assert_eq!(
+            replacement("#11223380", "#aabbcc"),
+            Ok("#aabbcc80".to_string())
+        );
+        assert_eq!(
+            replacement("#112233CC", "#aabbcc"),
+            Ok("#aabbcccc".to_string())
+        );
+        assert_eq!(
+            replacement("#112233cc", "#AABBCC"),
+            Ok("#AABBCCCC".to_string())
+        );
+        assert_eq!(replacement("#123c", "#aabbcc"), Ok("#aabbcccc".to_string()));
+    }
+
+    #[test]
+    fn short_colours_keep_their_case() {
+        assert_eq!(
+            replacement("#112233CC", "#abc"),
+            Ok("#aabbcccc".to_string())
+        );
+        assert_eq!(
+            replacement("#112233cc", "#ABC"),
+            Ok("#AABBCCCC".to_string())
+        );
+        assert_eq!(
+            replacement("#11223380", "#123"),
+            Ok("#11223380".to_string())
+        );
+    }
+
+    #[test]
+    fn new_alpha_or_opaque_colour() {
+        // The new colour's own alpha replaces the old one
+        assert_eq!(
+            replacement("#11223380", "#aabbcc40"),
+            Ok("#aabbcc40".to_string())
+        );
+        assert_eq!(replacement("#11223380", "#abc4"), Ok("#abc4".to_string()));
+
+        // There is no alpha to keep if the old colour is opaque
+        assert_eq!(replacement("#112233", "#abc"), Ok("#abc".to_string()));
+        assert_eq!(
+            replacement("#112233FF", "#aabbcc"),
+            Ok("#aabbcc".to_string())
+        );
+
+        assert_eq!(
+            replacement("#112233", "red"),
+            Err(
+                "'red' is not a valid hex colour (Expected #RGB, #RGBA, #RRGGBB or #RRGGBBAA)"
+                    .to_string()
+            )
+        );
+    }
 }
@@ -3,16 +3,55 @@
 #![warn(clippy::unwrap_used)]
 #![warn(clippy::expect_used)]
 
+use std::process::ExitCode;
+
+use crate::cli::{CLI, Command, parse_cli};
+use crate::commands::run_command;
+
 mod arguments;
+mod backup;
+mod cli;
+mod colour;
+mod commands;
 mod config;
+mod diagnostics;
+mod diff;
 mod errors;
+mod expression;
 mod file;
 mod functions;
+mod parser;
 mod regex;
+mod schema;
+mod scheme;
+mod theme;
+
+fn main() -> ExitCode {
+    let mut cli = match parse_cli(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::from(e.exit_code());
+        }
+    };
+
+    // Switching theme is the same as applying with --theme, then saving the theme to the config
+    if let Command::Switch(name) = &cli.command {
+        cli.theme = Some(name.clone());
+    }
+
+    let command = cli.command.clone();
+    let _ = CLI.set(cli);
 
-fn main() {
-    // Modify the files accordinig to the template text, then print any errors that occur
-    if let Err(e) = file::modify_files() {
-        eprintln!("{e}");
+    // Run the command, then print any errors that occur
+    match run_command(&command) {
+        Ok(exit_code) => exit_code,
+        Err(e) => {
+            eprintln!("{e}");
+            if let Some(help) = e.help() {
+                eprintln!("help: {help}");
+            }
+            ExitCode::from(e.exit_code())
+        }
     }
 }
@@ -0,0 +1,571 @@
+use std::fmt::Display;
+use std::ops::Range;
+
+use crate::config::FUNCTION_CHAR;
+use crate::errors::DotfilesError;
+use crate::file::MatchedText;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgumentKind {
+    // 'pattern' (Backslashes are kept, so regex escapes work, apart from \' which is an apostrophe)
+    Pattern(String),
+    // "string" (With escapes such as \", \\, \n and \t)
+    String(String),
+    // A variable from the theme (Nested values are written as fonts.mono)
+    Identifier(String),
+    // A whole number, such as 8
+    Int(i64),
+    // A number with a decimal point, where percentages are divided by 100 (So 10% is 0.1)
+    Float(f64),
+    // A literal hex colour, such as #A9B1D6
+    Colour(String),
+    // A call to a helper function, whose result is the argument, such as concat("a", b)
+    Call(Box<FunctionCall>),
+}
+
+impl ArgumentKind {
+    pub const fn kind_name(&self) -> &'static str {
+        match self {
+            Self::Pattern(_) => "a pattern",
+            Self::String(_) => "a string",
+            Self::Identifier(_) => "an identifier",
+            Self::Int(_) | Self::Float(_) => "a number",
+            Self::Colour(_) => "a colour",
+            Self::Call(_) => "a function call",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Argument {
+    pub kind: ArgumentKind,
+    // The argument as it was written, and its position in the file
+    pub text: String,
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionCall {
+    pub name: String,
+    pub name_span: Range<usize>,
+    pub args: Vec<Argument>,
+    // The whole call as it was written, and its position in the file
+    pub text: String,
+    pub span: Range<usize>,
+}
+
+impl Display for FunctionCall {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    OpenBracket,
+    CloseBracket,
+    Comma,
+    Argument(ArgumentKind),
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    // Position in the text (In bytes), and the position of the text within the file
+    position: usize,
+    file_offset: usize,
+}
+
+pub fn parse_template(template: &MatchedText) -> Result<Vec<FunctionCall>, DotfilesError> {
+    let mut parser = Parser {
+        text: &template.text,
+        position: 0,
+        file_offset: template.range.start,
+    };
+
+    // Anything which isn't a function call is a comment, so skip to each function call
+    let mut calls = Vec::new();
+    while let Some(call_start) = parser.find_next_call() {
+        calls.push(parser.parse_call(call_start)?);
+    }
+
+    Ok(calls)
+}
+
+// Parse text which is a single argument, such as a theme value
+pub fn parse_expression(text: &str) -> Result<Argument, DotfilesError> {
+    let mut parser = Parser {
+        text,
+        position: 0,
+        file_offset: 0,
+    };
+
+    let argument = parser.parse_argument()?;
+
+    // Make sure nothing comes after the argument
+    parser.skip_whitespace();
+    if parser.position < text.len() {
+        return Err(parser.error(
+            parser.position..text.len(),
+            "Unexpected text after the argument".to_string(),
+        ));
+    }
+
+    Ok(argument)
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    const fn file_span(&self, range: Range<usize>) -> Range<usize> {
+        (self.file_offset + range.start)..(self.file_offset + range.end)
+    }
+
+    fn error(&self, range: Range<usize>, message: String) -> DotfilesError {
+        DotfilesError::ParseError(message).at(&self.file_span(range))
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, predicate: F) -> &str {
+        let start = self.position;
+        while self.peek().is_some_and(&predicate) {
+            self.bump();
+        }
+
+        &self.text[start..self.position]
+    }
+
+    fn find_next_call(&mut self) -> Option<usize> {
+        // A function call is the function character, then a name (The brackets are checked by parse_call, so a call without them is an error rather than a comment)
+        while self.position < self.text.len() {
+            let start = self.position;
+
+            // Not straight after a word though, so an email address in a comment isn't a call
+            let after_word = self.text[..start]
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_ascii_alphanumeric());
+
+            if self.bump() == Some(FUNCTION_CHAR)
+                && !after_word
+                && self.peek().is_some_and(|c| c.is_ascii_alphabetic())
+            {
+                self.position = start;
+                return Some(start);
+            }
+        }
+
+        None
+    }
+
+    fn parse_call(&mut self, call_start: usize) -> Result<FunctionCall, DotfilesError> {
+        // Skip the function character
+        self.bump();
+
+        let name_start = self.position;
+        self.take_while(is_name_char);
+
+        self.parse_call_args(call_start, name_start..self.position)
+    }
+
+    fn parse_call_args(
+        &mut self,
+        call_start: usize,
+        name_range: Range<usize>,
+    ) -> Result<FunctionCall, DotfilesError> {
+        let name = self.text[name_range.clone()].to_string();
+
+        // Parse the arguments, which are separated by commas
+        let mut args = Vec::new();
+        self.expect_token(&Token::OpenBracket, &format!("'(' after '{name}'"))?;
+        self.skip_whitespace();
+        if self.peek() == Some(')') {
+            self.bump();
+        } else {
+            loop {
+                args.push(self.parse_argument()?);
+
+                let (token, range) = self.next_token()?;
+                match token {
+                    Some(Token::Comma) => {}
+                    Some(Token::CloseBracket) => break,
+                    _ => {
+                        return Err(self.error(
+                            range,
+                            format!("Expected ',' or ')' after argument in call to '{name}'"),
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(FunctionCall {
+            name,
+            name_span: self.file_span(name_range),
+            args,
+            text: self.text[call_start..self.position].to_string(),
+            span: self.file_span(call_start..self.position),
+        })
+    }
+
+    fn parse_argument(&mut self) -> Result<Argument, DotfilesError> {
+        let (token, range) = self.next_token()?;
+
+        match token {
+            // An identifier followed by a bracket is a call to a helper function
+            Some(Token::Argument(ArgumentKind::Identifier(_))) if self.peek() == Some('(') => {
+                let call = self.parse_call_args(range.start, range)?;
+
+                Ok(Argument {
+                    text: call.text.clone(),
+                    span: call.span.clone(),
+                    kind: ArgumentKind::Call(Box::new(call)),
+                })
+            }
+            Some(Token::Argument(kind)) => Ok(Argument {
+                kind,
+                text: self.text[range.clone()].to_string(),
+                span: self.file_span(range),
+            }),
+            _ => Err(self.error(range, "Expected an argument".to_string())),
+        }
+    }
+
+    fn expect_token(&mut self, expected: &Token, description: &str) -> Result<(), DotfilesError> {
+        let (token, range) = self.next_token()?;
+
+        if token.as_ref() == Some(expected) {
+            Ok(())
+        } else {
+            Err(self.error(range, format!("Expected {description}")))
+        }
+    }
+
+    fn next_token(&mut self) -> Result<(Option<Token>, Range<usize>), DotfilesError> {
+        self.skip_whitespace();
+
+        let start = self.position;
+        let Some(c) = self.bump() else {
+            return Ok((None, start..start));
+        };
+
+        let token = match c {
+            '(' => Token::OpenBracket,
+            ')' => Token::CloseBracket,
+            ',' => Token::Comma,
+            '\'' => Token::Argument(ArgumentKind::Pattern(self.parse_quoted(start, '\'')?)),
+            '"' => Token::Argument(ArgumentKind::String(self.parse_quoted(start, '"')?)),
+            '#' => {
+                self.take_while(|c| c.is_ascii_alphanumeric());
+                Token::Argument(ArgumentKind::Colour(
+                    self.text[start..self.position].to_string(),
+                ))
+            }
+            c if c.is_ascii_digit()
+                || (c == '-' && self.peek().is_some_and(|c| c.is_ascii_digit())) =>
+            {
+                self.take_while(|c| c.is_ascii_digit() || c == '.');
+                let number_text = &self.text[start..self.position];
+                let invalid_number = || {
+                    self.error(
+                        start..self.position,
+                        format!("'{number_text}' is not a valid number"),
+                    )
+                };
+
+                // Percentages are stored as a fraction, and numbers without a decimal point are whole numbers
+                if self.peek() == Some('%') {
+                    let number = number_text.parse::<f64>().map_err(|_| invalid_number())?;
+                    self.bump();
+                    Token::Argument(ArgumentKind::Float(number / 100.0))
+                } else if number_text.contains('.') {
+                    let number = number_text.parse::<f64>().map_err(|_| invalid_number())?;
+                    Token::Argument(ArgumentKind::Float(number))
+                } else {
+                    let number = number_text.parse::<i64>().map_err(|_| invalid_number())?;
+                    Token::Argument(ArgumentKind::Int(number))
+                }
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                self.take_while(is_identifier_char);
+                Token::Argument(ArgumentKind::Identifier(
+                    self.text[start..self.position].to_string(),
+                ))
+            }
+            c => {
+                return Err(self.error(start..self.position, format!("Unexpected character '{c}'")));
+            }
+        };
+
+        Ok((Some(token), start..self.position))
+    }
+
+    fn parse_quoted(&mut self, start: usize, quote: char) -> Result<String, DotfilesError> {
+        let mut value = String::new();
+
+        loop {
+            match self.bump() {
+                None => {
+                    return Err(
+                        self.error(start..self.position, format!("Missing closing {quote}"))
+                    );
+                }
+                Some(c) if c == quote => return Ok(value),
+                Some('\\') => match (quote, self.bump()) {
+                    (_, None) => {
+                        return Err(
+                            self.error(start..self.position, format!("Missing closing {quote}"))
+                        );
+                    }
+                    (_, Some(c)) if c == quote => value.push(c),
+                    // Patterns keep their backslashes, since they are needed by the regex
+                    ('\'', Some(c)) => {
+                        value.push('\\');
+                        value.push(c);
+                    }
+                    (_, Some('n')) => value.push('\n'),
+                    (_, Some('t')) => value.push('\t'),
+                    (_, Some(c)) => value.push(c),
+                },
+                Some(c) => value.push(c),
+            }
+        }
+    }
+}
+
+const fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+// Identifiers can also have dots in them, for values in nested tables such as fonts.mono
+const fn is_identifier_char(c: char) -> bool {
+    is_name_char(c) || c == '.'
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Vec<FunctionCall> {
+        parse_template(&MatchedText {
+            range: 0..text.len(),
+            text: text.to_string(),
+        })
+        .expect("template should parse")
+    }
+
+    fn kinds(call: &FunctionCall) -> Vec<ArgumentKind> {
+        call.args.iter().map(|arg| arg.kind.clone()).collect()
+    }
+
+    fn error_message(error: DotfilesError) -> String {
+        error.unspanned().to_string()
+    }
+
+    #[test]
+    fn pattern_with_braces_and_comma() {
+        let calls = parse(r"@replace('[0-9]{1,3}', size)");
+
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].name, "replace");
+        assert_eq!(
+            kinds(&calls[0]),
+            [
+                ArgumentKind::Pattern("[0-9]{1,3}".to_string()),
+                ArgumentKind::Identifier("size".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn string_with_comma_and_bracket() {
+        let calls = parse(r#"@replace('font = .*', "font = Fira, Mono)")"#);
+
+        assert_eq!(
+            kinds(&calls[0]),
+            [
+                ArgumentKind::Pattern("font = .*".to_string()),
+                ArgumentKind::String("font = Fira, Mono)".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn string_escapes() {
+        let argument = parse_expression(r#""a\"b\\c\nd\te""#).expect("string should parse");
+
+        assert_eq!(
+            argument.kind,
+            ArgumentKind::String("a\"b\\c\nd\te".to_string())
+        );
+    }
+
+    #[test]
+    fn pattern_keeps_backslashes() {
+        let argument = parse_expression(r"'it\'s \d+\.\d'").expect("pattern should parse");
+
+        assert_eq!(
+            argument.kind,
+            ArgumentKind::Pattern(r"it's \d+\.\d".to_string())
+        );
+    }
+
+    #[test]
+    fn numbers() {
+        let number = |text: &str| parse_expression(text).expect("number should parse").kind;
+
+        assert_eq!(number("8"), ArgumentKind::Int(8));
+        assert_eq!(number("-5"), ArgumentKind::Int(-5));
+        assert_eq!(number("0.5"), ArgumentKind::Float(0.5));
+        assert_eq!(number("-0.25"), ArgumentKind::Float(-0.25));
+        assert_eq!(number("10%"), ArgumentKind::Float(0.1));
+        assert_eq!(number("1.0"), ArgumentKind::Float(1.0));
+    }
+
+    #[test]
+    fn colours_and_identifiers() {
+        assert_eq!(
+            parse_expression("#A9B1D6").map(|arg| arg.kind).ok(),
+            Some(ArgumentKind::Colour("#A9B1D6".to_string()))
+        );
+        assert_eq!(
+            parse_expression("fonts.mono").map(|arg| arg.kind).ok(),
+            Some(ArgumentKind::Identifier("fonts.mono".to_string()))
+        );
+    }
+
+    #[test]
+    fn nested_calls() {
+        let calls = parse("@replace-col(lighten(mix(bg_col, #FFF, 50%), 10%))");
+        let [
+            Argument {
+                kind: ArgumentKind::Call(lighten),
+                ..
+            },
+        ] = calls[0].args.as_slice()
+        else {
+            panic!("expected a call to lighten, found {:?}", calls[0].args);
+        };
+
+        assert_eq!(lighten.name, "lighten");
+        assert_eq!(lighten.text, "lighten(mix(bg_col, #FFF, 50%), 10%)");
+        assert_eq!(lighten.args[1].kind, ArgumentKind::Float(0.1));
+
+        let ArgumentKind::Call(mix) = &lighten.args[0].kind else {
+            panic!("expected a call to mix, found {:?}", lighten.args[0]);
+        };
+        assert_eq!(
+            kinds(mix),
+            [
+                ArgumentKind::Identifier("bg_col".to_string()),
+                ArgumentKind::Colour("#FFF".to_string()),
+                ArgumentKind::Float(0.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_arguments() {
+        let calls = parse("@concat( )");
+
+        assert_eq!(calls[0].name, "concat");
+        assert!(calls[0].args.is_empty());
+    }
+
+    #[test]
+    fn text_between_calls_is_a_comment() {
+        let calls = parse("set the colour, email me@example.com @replace-col(a) and @upper (b)");
+
+        assert_eq!(
+            calls
+                .iter()
+                .map(|call| call.name.as_str())
+                .collect::<Vec<_>>(),
+            ["replace-col", "upper"]
+        );
+    }
+
+    #[test]
+    fn spans_are_positions_in_the_file() {
+        // The template starts at byte 100 of the file
+        let text = r#" @replace('x', "y")"#;
+        let calls = parse_template(&MatchedText {
+            range: 100..100 + text.len(),
+            text: text.to_string(),
+        })
+        .expect("template should parse");
+
+        let call = &calls[0];
+        assert_eq!(call.span, 101..119);
+        assert_eq!(call.name_span, 102..109);
+        assert_eq!(call.args[0].span, 110..113);
+        assert_eq!(call.args[0].text, "'x'");
+        assert_eq!(call.args[1].span, 115..118);
+        assert_eq!(call.args[1].text, "\"y\"");
+    }
+
+    #[test]
+    fn errors() {
+        let template_error = |text: &str| {
+            parse_template(&MatchedText {
+                range: 0..text.len(),
+                text: text.to_string(),
+            })
+            .map(|_| ())
+            .map_err(error_message)
+        };
+
+        assert_eq!(
+            template_error("@replace('x, y)"),
+            Err("Template could not be parsed: Missing closing '".to_string())
+        );
+        assert_eq!(
+            template_error("@replace('x' y)"),
+            Err(
+                "Template could not be parsed: Expected ',' or ')' after argument in call to 'replace'"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            template_error("@replace-col bg_col)"),
+            Err("Template could not be parsed: Expected '(' after 'replace-col'".to_string())
+        );
+        assert_eq!(
+            template_error("set @the colour"),
+            Err("Template could not be parsed: Expected '(' after 'the'".to_string())
+        );
+        assert_eq!(
+            template_error("@replace('x', ;)"),
+            Err("Template could not be parsed: Unexpected character ';'".to_string())
+        );
+        assert_eq!(
+            parse_expression("lighten(a, 10%) b").map_err(error_message),
+            Err("Template could not be parsed: Unexpected text after the argument".to_string())
+        );
+    }
+
+    #[test]
+    fn error_spans() {
+        let Err(DotfilesError::Spanned { range, .. }) = parse_template(&MatchedText {
+            range: 10..24,
+            text: "@replace('x', ;)".to_string(),
+        }) else {
+            panic!("expected an error with a position");
+        };
+
+        assert_eq!(range, 24..25);
+    }
+}
@@ -1,48 +1,12 @@
 use regex::Regex;
 
-use crate::config::FUNCTION_CHAR;
+use crate::colour::COLOUR_PATTERN;
 use crate::errors::DotfilesError;
 use crate::file::{MatchedText, open_file};
 use std::sync::LazyLock;
 
-pub static FUNCTION_REGEX: LazyLock<Result<Regex, DotfilesError>> = LazyLock::new(|| {
-    Ok(Regex::new(
-        format!("{FUNCTION_CHAR}(?<name>[a-zA-Z][A-Za-z\\d_\\-]*)(?<args>(?:\\\\.|[^)])+\\))")
-            .as_str(),
-    )?)
-});
-pub static PATTERN_REGEX: LazyLock<Result<Regex, DotfilesError>> =
-    LazyLock::new(|| Ok(Regex::new(r"'[^']+'")?));
-pub static STRING_OR_KEYWORD_REGEX: LazyLock<Result<Regex, DotfilesError>> =
-    LazyLock::new(|| Ok(Regex::new("('[^']+')|([a-zA-Z][\\w\\-]*)")?));
 pub static HEX_COLOUR_REGEX: LazyLock<Result<Regex, DotfilesError>> =
-    LazyLock::new(|| Ok(Regex::new("#[A-Za-z\\d]{6}")?));
-
-pub fn matches_pattern(arg: &str) -> Result<(), DotfilesError> {
-    let pattern_regex = (*PATTERN_REGEX).clone()?;
-
-    if pattern_regex.is_match(arg) {
-        Ok(())
-    } else {
-        Err(DotfilesError::RegexMatchError {
-            regex_str: pattern_regex.to_string(),
-            hay: arg.to_string(),
-        })
-    }
-}
-
-pub fn matches_keyword_or_string(arg: &str) -> Result<(), DotfilesError> {
-    let string_or_keyword_regex = (*STRING_OR_KEYWORD_REGEX).clone()?;
-
-    if string_or_keyword_regex.is_match(arg) {
-        Ok(())
-    } else {
-        Err(DotfilesError::RegexMatchError {
-            regex_str: string_or_keyword_regex.to_string(),
-            hay: arg.to_string(),
-        })
-    }
-}
+    LazyLock::new(|| Ok(Regex::new(COLOUR_PATTERN)?));
 
 pub fn get_single_match(regex: &Regex, text: MatchedText) -> Result<MatchedText, DotfilesError> {
     // Get the capture for this regex and text
@@ -0,0 +1,171 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::Deserialize;
+
+use crate::errors::DotfilesError;
+use crate::expression::Value;
+use crate::theme::{Theme, theme_name};
+
+// The keys which are part of every theme, rather than being variables
+const THEME_SETTINGS: [&str; 2] = ["name", "extends"];
+
+// The type which a variable in the schema has to be
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SchemaType {
+    Colour,
+    Number,
+    String,
+    Bool,
+}
+
+impl SchemaType {
+    const fn kind_name(self) -> &'static str {
+        match self {
+            Self::Colour => "a colour",
+            Self::Number => "a number",
+            Self::String => "a string",
+            Self::Bool => "a boolean",
+        }
+    }
+
+    const fn matches(self, value: &Value) -> bool {
+        match self {
+            Self::Colour => matches!(value, Value::Colour(_)),
+            Self::Number => matches!(value, Value::Int(_) | Value::Float(_)),
+            // A colour is written as text, so it can be used as a string
+            Self::String => matches!(value, Value::String(_) | Value::Colour(_)),
+            Self::Bool => matches!(value, Value::Bool(_)),
+        }
+    }
+}
+
+pub type Schema = HashMap<String, SchemaType>;
+
+// Make sure the theme has every variable in the schema, and that each one is the right type
+pub fn check_schema(theme: &Theme, schema: &Schema) -> Result<(), DotfilesError> {
+    // Sorted, so the problems are always listed in the same order
+    let problems = schema
+        .iter()
+        .collect::<BTreeMap<_, _>>()
+        .into_iter()
+        .filter_map(|(key, schema_type)| match theme.get(key) {
+            None => Some(format!(
+                "'{key}' is missing (It should be {})",
+                schema_type.kind_name()
+            )),
+            Some(value) if !schema_type.matches(value) => Some(format!(
+                "'{key}' should be {}, found {}",
+                schema_type.kind_name(),
+                value.kind_name()
+            )),
+            Some(_) => None,
+        })
+        .collect::<Vec<_>>();
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(DotfilesError::SchemaMismatch {
+            theme: theme_name(theme),
+            problems,
+        })
+    }
+}
+
+// Find the variables which some themes have but others don't, along with the themes which are missing them (Schemes are only compared with other schemes, since they have baseXX variables rather than hand-written ones)
+pub fn find_missing_keys(
+    themes: &[Theme],
+    schemes: &HashSet<String>,
+    schema: &Schema,
+) -> BTreeMap<String, Vec<String>> {
+    let (scheme_themes, other_themes): (Vec<_>, Vec<_>) = themes
+        .iter()
+        .partition(|theme| schemes.contains(&theme_name(theme)));
+
+    let mut missing_keys = BTreeMap::new();
+    for group in [other_themes, scheme_themes] {
+        for (key, missing_from) in find_missing_keys_in(&group, schema) {
+            missing_keys
+                .entry(key)
+                .or_insert_with(Vec::new)
+                .extend(missing_from);
+        }
+    }
+
+    missing_keys
+}
+
+fn find_missing_keys_in(themes: &[&Theme], schema: &Schema) -> BTreeMap<String, Vec<String>> {
+    let mut missing_keys = BTreeMap::new();
+
+    for theme in themes {
+        for key in theme.keys() {
+            // Settings aren't variables, and keys in the schema are already reported by check_schema
+            if THEME_SETTINGS.contains(&key.as_str())
+                || schema.contains_key(key)
+                || missing_keys.contains_key(key)
+            {
+                continue;
+            }
+
+            let missing_from = themes
+                .iter()
+                .filter(|other| !other.contains_key(key))
+                .map(|other| theme_name(other))
+                .collect::<Vec<_>>();
+            if !missing_from.is_empty() {
+                missing_keys.insert(key.clone(), missing_from);
+            }
+        }
+    }
+
+    missing_keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn theme(name: &str, keys: &[&str]) -> Theme {
+        keys.iter()
+            .map(|key| (key.to_string(), Value::Colour("#000000".to_string())))
+            .chain([("name".to_string(), Value::String(name.to_string()))])
+            .collect()
+    }
+
+    #[test]
+    fn missing_keys() {
+        let themes = [theme("a", &["bg", "fg"]), theme("b", &["bg"])];
+        let missing_keys = find_missing_keys(&themes, &HashSet::new(), &Schema::new());
+
+        assert_eq!(
+            missing_keys,
+            BTreeMap::from([("fg".to_string(), vec!["b".to_string()])])
+        );
+    }
+
+    #[test]
+    fn missing_keys_skips_schema() {
+        let themes = [theme("a", &["bg", "fg"]), theme("b", &["bg"])];
+        let schema = Schema::from([("fg".to_string(), SchemaType::Colour)]);
+
+        assert!(find_missing_keys(&themes, &HashSet::new(), &schema).is_empty());
+    }
+
+    #[test]
+    fn missing_keys_compares_schemes_with_schemes() {
+        let themes = [
+            theme("a", &["bg", "accent"]),
+            theme("b", &["bg", "accent"]),
+            theme("base16", &["base00", "bg"]),
+            theme("base24", &["base00", "base10", "bg"]),
+        ];
+        let schemes = HashSet::from(["base16".to_string(), "base24".to_string()]);
+
+        assert_eq!(
+            find_missing_keys(&themes, &schemes, &Schema::new()),
+            BTreeMap::from([("base10".to_string(), vec!["base16".to_string()])])
+        );
+    }
+}
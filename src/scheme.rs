@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+
+use crate::cli::info;
+use crate::colour::Colour;
+use crate::config::config;
+use crate::errors::DotfilesError;
+use crate::expression::Value;
+use crate::file::open_file;
+use crate::theme::{Theme, ThemeSource, files_with_extensions};
+
+// Load a base16 or base24 scheme as a theme, with a variable for each colour (base00 to base0F, and base10 to base17 for base24)
+pub fn load_scheme(path: &Path) -> Result<Theme, DotfilesError> {
+    let path_str = path.display().to_string();
+    let scheme_error = |e| DotfilesError::ThemeFileError {
+        path: path_str.clone(),
+        error: Box::new(e),
+    };
+
+    let yaml = open_file(&path_str)
+        .and_then(|text| {
+            serde_norway::from_str::<serde_norway::Value>(&text)
+                .map_err(|e| DotfilesError::YamlReadError(e.to_string()))
+        })
+        .map_err(scheme_error)?;
+
+    // Newer schemes keep the colours in a palette, older ones have them at the top level
+    let palette = yaml.get("palette").unwrap_or(&yaml);
+    let Some(palette) = palette.as_mapping() else {
+        return Err(scheme_error(DotfilesError::YamlReadError(
+            "Expected a mapping of base00 to base0F".to_string(),
+        )));
+    };
+
+    let mut theme = Theme::new();
+    for (key, value) in palette {
+        let (Some(key), Some(value)) = (key.as_str(), value.as_str()) else {
+            continue;
+        };
+
+        // Only keep the colours, written as base0A and #RRGGBB (Older schemes leave out the #)
+        let Some(digits) = key
+            .strip_prefix("base")
+            .filter(|digits| digits.len() == 2 && digits.chars().all(|c| c.is_ascii_hexdigit()))
+        else {
+            continue;
+        };
+
+        let colour = format!("#{}", value.trim_start_matches('#'));
+        Colour::from_hex(&colour).map_err(scheme_error)?;
+        theme.insert(
+            format!("base{}", digits.to_uppercase()),
+            Value::Colour(colour),
+        );
+    }
+
+    if theme.is_empty() {
+        return Err(scheme_error(DotfilesError::YamlReadError(
+            "No base00 to base0F colours were found".to_string(),
+        )));
+    }
+
+    // The theme is named after the file (The scheme's own name often has spaces in it)
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    theme.insert("name".to_string(), Value::String(name));
+
+    Ok(theme)
+}
+
+pub fn load_scheme_files(dir: &Path) -> Result<Vec<ThemeSource>, DotfilesError> {
+    // Every YAML file in the folder is a scheme
+    files_with_extensions(dir, &["yaml", "yml"])?
+        .into_iter()
+        .map(|path| {
+            Ok(ThemeSource {
+                theme: load_scheme(&path)?,
+                source: format!("'{}'", path.display()),
+            })
+        })
+        .collect()
+}
+
+pub fn import_scheme(path: &str) -> Result<(), DotfilesError> {
+    let config = config()?;
+    let mut theme = load_scheme(Path::new(path))?;
+    let name = theme
+        .remove("name")
+        .map(|name| name.to_string())
+        .unwrap_or_default();
+
+    // Don't replace a theme which already exists
+    if config.get_theme_hashmap().contains_key(&name) {
+        return Err(DotfilesError::ThemeAlreadyExists(name));
+    }
+
+    // Save the scheme as a theme file, which is named after the scheme's file
+    let themes_dir = config.get_themes_dir();
+    fs::create_dir_all(&themes_dir)?;
+    let theme_path = Path::new(&themes_dir).join(format!("{name}.toml"));
+    let colours = theme
+        .into_iter()
+        .map(|(key, colour)| (key, colour.to_string()))
+        .collect::<BTreeMap<_, _>>();
+    let contents = format!("# Imported from '{path}'\n{}", toml::to_string(&colours)?);
+
+    // Don't replace a theme file either, which may have a different name set inside it
+    let mut theme_file = match File::create_new(&theme_path) {
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            return Err(DotfilesError::ThemeFileExists(
+                theme_path.display().to_string(),
+            ));
+        }
+        theme_file => theme_file?,
+    };
+    theme_file.write_all(contents.as_bytes())?;
+
+    info!("Imported theme '{name}' into {}", theme_path.display());
+
+    Ok(())
+}
@@ -0,0 +1,555 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::diagnostics::closest_match;
+use crate::errors::DotfilesError;
+use crate::expression::{HELPER_NAMES, Value, evaluate};
+use crate::file::open_file;
+use crate::parser::{ArgumentKind, parse_expression};
+
+// The variables of a theme, where nested tables are flattened into keys such as fonts.mono
+pub type Theme = HashMap<String, Value>;
+
+// For a theme, the theme which each of its inherited variables came from
+pub type InheritedKeys = HashMap<String, String>;
+
+pub fn theme_from_toml(table: toml::Table) -> Theme {
+    let mut theme = Theme::new();
+    add_toml_table(&mut theme, "", table);
+
+    theme
+}
+
+fn add_toml_table(theme: &mut Theme, prefix: &str, table: toml::Table) {
+    for (key, value) in table {
+        let key = format!("{prefix}{key}");
+        match value {
+            toml::Value::Table(table) => add_toml_table(theme, &format!("{key}."), table),
+            value => {
+                theme.insert(key, toml_to_value(value));
+            }
+        }
+    }
+}
+
+fn toml_to_value(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(text) => Value::String(text),
+        toml::Value::Integer(number) => Value::Int(number),
+        toml::Value::Float(number) => Value::Float(number),
+        toml::Value::Boolean(boolean) => Value::Bool(boolean),
+        toml::Value::Array(values) => Value::List(values.into_iter().map(toml_to_value).collect()),
+        toml::Value::Table(table) => Value::String(table.to_string()),
+        toml::Value::Datetime(datetime) => Value::String(datetime.to_string()),
+    }
+}
+
+pub fn theme_name(theme: &Theme) -> String {
+    theme
+        .get("name")
+        .map(ToString::to_string)
+        .unwrap_or_default()
+}
+
+// Where a theme was defined, so duplicate themes can be reported
+#[derive(Debug, Clone)]
+pub struct ThemeSource {
+    pub theme: Theme,
+    pub source: String,
+}
+
+// The files in a folder with one of the extensions, sorted so they are always listed in the same order (A missing folder has no files)
+pub fn files_with_extensions(
+    dir: &Path,
+    extensions: &[&str],
+) -> Result<Vec<PathBuf>, DotfilesError> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut paths = fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|extension| extensions.iter().any(|allowed| extension == *allowed))
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    Ok(paths)
+}
+
+pub fn load_theme_files(dir: &Path) -> Result<Vec<ThemeSource>, DotfilesError> {
+    // Every TOML file in the folder is a theme
+    files_with_extensions(dir, &["toml"])?
+        .into_iter()
+        .map(|path| {
+            let path_str = path.display().to_string();
+            let mut theme = open_file(&path_str)
+                .and_then(|text| Ok(theme_from_toml(toml::from_str(&text)?)))
+                .map_err(|e| DotfilesError::ThemeFileError {
+                    path: path_str.clone(),
+                    error: Box::new(e),
+                })?;
+
+            // The theme is named after the file, unless it has a name
+            if !theme.contains_key("name") {
+                let name = path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                theme.insert("name".to_string(), Value::String(name));
+            }
+
+            Ok(ThemeSource {
+                theme,
+                source: format!("'{path_str}'"),
+            })
+        })
+        .collect()
+}
+
+pub fn check_duplicate_themes(themes: &[ThemeSource]) -> Result<(), DotfilesError> {
+    let mut sources = HashMap::new();
+    for ThemeSource { theme, source } in themes {
+        let name = theme_name(theme);
+
+        if let Some(first) = sources.insert(name.clone(), source) {
+            return Err(DotfilesError::DuplicateTheme {
+                name,
+                first: first.clone(),
+                second: source.clone(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+pub fn resolve_inheritance(
+    themes: &[Theme],
+) -> Result<(Vec<Theme>, HashMap<String, InheritedKeys>), DotfilesError> {
+    let themes_by_name = themes
+        .iter()
+        .map(|theme| (theme_name(theme), theme))
+        .collect::<HashMap<_, _>>();
+
+    let mut resolved_themes = Vec::new();
+    let mut inherited_by_theme = HashMap::new();
+    for theme in themes {
+        let name = theme_name(theme);
+
+        // Follow extends up to the theme which doesn't extend anything, stopping if a theme comes up twice
+        let mut chain = vec![theme];
+        while let Some(parent_name) = chain
+            .last()
+            .and_then(|current| current.get("extends"))
+            .map(ToString::to_string)
+        {
+            let mut chain_names = chain
+                .iter()
+                .map(|theme| theme_name(theme))
+                .collect::<Vec<_>>();
+            if chain_names.contains(&parent_name) {
+                chain_names.push(parent_name);
+                return Err(DotfilesError::ThemeExtendsCycle(chain_names));
+            }
+
+            let Some(parent) = themes_by_name.get(&parent_name) else {
+                return Err(DotfilesError::ParentThemeNotFound {
+                    theme: chain_names.last().cloned().unwrap_or_default(),
+                    suggestion: closest_match(
+                        &parent_name,
+                        themes_by_name.keys().map(String::as_str),
+                    ),
+                    parent: parent_name,
+                });
+            };
+            chain.push(parent);
+        }
+
+        // Start from the furthest ancestor, so each theme overrides the themes it extends
+        let mut resolved = Theme::new();
+        let mut inherited = HashMap::new();
+        for ancestor in chain.iter().rev() {
+            let ancestor_name = theme_name(ancestor);
+
+            for (key, value) in ancestor
+                .iter()
+                .filter(|(key, _)| !matches!(key.as_str(), "name" | "extends"))
+            {
+                resolved.insert(key.clone(), value.clone());
+                if ancestor_name == name {
+                    inherited.remove(key);
+                } else {
+                    inherited.insert(key.clone(), ancestor_name.clone());
+                }
+            }
+        }
+        resolved.insert("name".to_string(), Value::String(name.clone()));
+
+        resolved_themes.push(resolved);
+        inherited_by_theme.insert(name, inherited);
+    }
+
+    Ok((resolved_themes, inherited_by_theme))
+}
+
+pub fn evaluate_theme(theme: &Theme) -> Result<Theme, DotfilesError> {
+    let evaluator = ThemeEvaluator {
+        theme,
+        name: theme_name(theme),
+        evaluated: RefCell::new(HashMap::new()),
+        stack: RefCell::new(Vec::new()),
+    };
+
+    // Go through the keys in order, so the same error is always reported first
+    let mut keys = theme
+        .keys()
+        .filter(|key| *key != "name")
+        .collect::<Vec<_>>();
+    keys.sort();
+
+    let mut evaluated = theme.clone();
+    for key in keys {
+        evaluated.insert(key.clone(), evaluator.value_of(key)?);
+    }
+
+    Ok(evaluated)
+}
+
+struct ThemeEvaluator<'a> {
+    theme: &'a Theme,
+    name: String,
+    // The values which have been worked out so far, and the keys which are being worked out (To find cycles)
+    evaluated: RefCell<Theme>,
+    stack: RefCell<Vec<String>>,
+}
+
+impl ThemeEvaluator<'_> {
+    fn value_of(&self, key: &str) -> Result<Value, DotfilesError> {
+        if let Some(value) = self.evaluated.borrow().get(key) {
+            return Ok(value.clone());
+        }
+
+        // A key which is already being worked out depends on itself
+        if let Some(start) = self
+            .stack
+            .borrow()
+            .iter()
+            .position(|stacked| stacked == key)
+        {
+            let mut chain = self.stack.borrow()[start..].to_vec();
+            chain.push(key.to_string());
+
+            return Err(DotfilesError::ThemeValueCycle {
+                theme: self.name.clone(),
+                chain,
+            });
+        }
+
+        let Some(raw_value) = self.theme.get(key) else {
+            return Err(DotfilesError::ArgNotFound {
+                arg: key.to_string(),
+                theme: self.name.clone(),
+                suggestion: closest_match(
+                    key,
+                    self.theme
+                        .keys()
+                        .map(String::as_str)
+                        .filter(|key| *key != "name"),
+                ),
+            });
+        };
+
+        // Work out the values which this one uses first
+        self.stack.borrow_mut().push(key.to_string());
+        let value = self
+            .evaluate_value(raw_value)
+            .map_err(|e| match e.unspanned() {
+                // Only point at the key where the error happened, rather than every key which uses it
+                e @ (DotfilesError::ThemeValueError { .. }
+                | DotfilesError::ThemeValueCycle { .. }) => e,
+                e => DotfilesError::ThemeValueError {
+                    theme: self.name.clone(),
+                    key: key.to_string(),
+                    error: Box::new(e),
+                },
+            });
+        self.stack.borrow_mut().pop();
+
+        let value = value?;
+        self.evaluated
+            .borrow_mut()
+            .insert(key.to_string(), value.clone());
+
+        Ok(value)
+    }
+
+    fn evaluate_value(&self, raw_value: &Value) -> Result<Value, DotfilesError> {
+        // Only text can use other values, anything else (Such as a number) is kept as it is
+        let Value::String(raw_value) = raw_value else {
+            return Ok(raw_value.clone());
+        };
+
+        // A value which is a single helper function call is evaluated, such as lighten(primary_col, 10%) (Other calls, such as rgba(0, 0, 0, 0.5), are kept as text)
+        if let Ok(expression) = parse_expression(raw_value)
+            && let ArgumentKind::Call(call) = &expression.kind
+            && HELPER_NAMES.contains(&call.name.as_str())
+        {
+            let variables = |name: &str| self.value_of(name);
+            return evaluate(&expression, &variables);
+        }
+
+        // A value which is only ${key} is the value of key, keeping its type
+        if let Some(key) = raw_value
+            .strip_prefix("${")
+            .and_then(|rest| rest.strip_suffix('}'))
+            .filter(|key| !key.contains(['{', '}']))
+        {
+            return self.value_of(key.trim());
+        }
+
+        // Otherwise ${key} is replaced with the value of key, such as "${font} Bold"
+        let mut value = String::new();
+        let mut rest = raw_value.as_str();
+        while let Some(start) = rest.find("${") {
+            let Some(length) = rest[start..].find('}') else {
+                return Err(DotfilesError::ParseError(format!(
+                    "Missing closing }} in '{raw_value}'"
+                )));
+            };
+
+            value.push_str(&rest[..start]);
+            value.push_str(
+                &self
+                    .value_of(rest[start + 2..start + length].trim())?
+                    .to_string(),
+            );
+            rest = &rest[start + length + 1..];
+        }
+        value.push_str(rest);
+
+        Ok(Value::from_theme_value(value))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    fn themes(text: &str) -> Vec<Theme> {
+        let tables = toml::from_str::<HashMap<String, Vec<toml::Table>>>(text)
+            .expect("themes should be valid TOML");
+
+        tables["themes"]
+            .iter()
+            .map(|table| theme_from_toml(table.clone()))
+            .collect()
+    }
+
+    fn theme(text: &str) -> Theme {
+        themes(&format!("[[themes]]\nname = \"t\"\n{text}"))
+            .pop()
+            .expect("there should be a theme")
+    }
+
+    // Resolve and evaluate the themes, returning the one with this name
+    fn resolved(themes: &[Theme], name: &str) -> Theme {
+        let (resolved, _) = resolve_inheritance(themes).expect("themes should resolve");
+
+        resolved
+            .iter()
+            .find(|theme| theme_name(theme) == name)
+            .map(|theme| evaluate_theme(theme).expect("theme should evaluate"))
+            .expect("theme should exist")
+    }
+
+    fn string(text: &str) -> Value {
+        Value::String(text.to_string())
+    }
+
+    #[test]
+    fn extends_several_levels() {
+        let themes = themes(
+            r##"
+            [[themes]]
+            name = "base"
+            bg = "#000000"
+            fg = "#ffffff"
+            gap = 4
+
+            [[themes]]
+            name = "middle"
+            extends = "base"
+            fg = "#eeeeee"
+
+            [[themes]]
+            name = "top"
+            extends = "middle"
+            gap = 8
+            "##,
+        );
+        let (_, inherited) = resolve_inheritance(&themes).expect("themes should resolve");
+        let top = resolved(&themes, "top");
+
+        assert_eq!(top["bg"], Value::Colour("#000000".to_string()));
+        assert_eq!(top["fg"], Value::Colour("#eeeeee".to_string()));
+        assert_eq!(top["gap"], Value::Int(8));
+        assert!(!top.contains_key("extends"));
+
+        // Each inherited variable records the theme it came from, and overridden ones aren't inherited
+        assert_eq!(
+            inherited["top"],
+            InheritedKeys::from([
+                ("bg".to_string(), "base".to_string()),
+                ("fg".to_string(), "middle".to_string()),
+            ])
+        );
+        assert!(inherited["base"].is_empty());
+    }
+
+    #[test]
+    fn extends_errors() {
+        let cycle = themes(
+            r#"
+            [[themes]]
+            name = "a"
+            extends = "b"
+
+            [[themes]]
+            name = "b"
+            extends = "a"
+            "#,
+        );
+        let Err(DotfilesError::ThemeExtendsCycle(chain)) = resolve_inheritance(&cycle) else {
+            panic!("expected an extends cycle");
+        };
+        assert_eq!(chain, ["a", "b", "a"]);
+
+        let missing = themes(
+            r#"
+            [[themes]]
+            name = "dark"
+
+            [[themes]]
+            name = "a"
+            extends = "darc"
+            "#,
+        );
+        let Err(DotfilesError::ParentThemeNotFound {
+            theme,
+            parent,
+            suggestion,
+        }) = resolve_inheritance(&missing)
+        else {
+            panic!("expected a missing parent");
+        };
+        assert_eq!(
+            (theme.as_str(), parent.as_str(), suggestion.as_deref()),
+            ("a", "darc", Some("dark"))
+        );
+    }
+
+    #[test]
+    fn child_overrides_are_used_by_parent_values() {
+        let themes = themes(
+            r##"
+            [[themes]]
+            name = "parent"
+            bg = "#000000"
+            border = "with_alpha(bg, 50%)"
+            label = "${bg} border"
+
+            [[themes]]
+            name = "child"
+            extends = "parent"
+            bg = "#FF0000"
+            "##,
+        );
+        let parent = resolved(&themes, "parent");
+        let child = resolved(&themes, "child");
+
+        assert_eq!(parent["border"], Value::Colour("#00000080".to_string()));
+        assert_eq!(child["border"], Value::Colour("#FF000080".to_string()));
+        assert_eq!(child["label"], string("#FF0000 border"));
+    }
+
+    #[test]
+    fn interpolation() {
+        let theme = evaluate_theme(&theme(
+            r##"
+            a = "x"
+            b = "y"
+            gap = 4
+            both = "${a}${b}"
+            spaced = "${ a } and ${b}!"
+            inner_gap = "${gap}"
+            colour = "#${a}"
+            shadow = "rgba(0, 0, 0, 0.5)"
+            "##,
+        ))
+        .expect("theme should evaluate");
+
+        assert_eq!(theme["both"], string("xy"));
+        assert_eq!(theme["spaced"], string("x and y!"));
+
+        // A value which is only ${key} keeps the type of key
+        assert_eq!(theme["inner_gap"], Value::Int(4));
+
+        // Calls which aren't helpers are kept as text
+        assert_eq!(theme["shadow"], string("rgba(0, 0, 0, 0.5)"));
+        assert_eq!(theme["colour"], string("#x"));
+    }
+
+    #[test]
+    fn value_cycles() {
+        let Err(DotfilesError::ThemeValueCycle { theme, chain }) = evaluate_theme(&theme(
+            r#"
+            a = "${b}"
+            b = "lighten(c, 10%)"
+            c = "${a} text"
+            "#,
+        )) else {
+            panic!("expected a value cycle");
+        };
+
+        assert_eq!(theme, "t");
+        assert_eq!(chain, ["a", "b", "c", "a"]);
+    }
+
+    #[test]
+    fn missing_values() {
+        let Err(DotfilesError::ThemeValueError {
+            theme: name,
+            key,
+            error,
+        }) = evaluate_theme(&theme(
+            r##"
+                primary = "#000000"
+                label = "${primray} text"
+                "##,
+        ))
+        else {
+            panic!("expected a missing value");
+        };
+
+        assert_eq!((name.as_str(), key.as_str()), ("t", "label"));
+        assert!(matches!(
+            *error,
+            DotfilesError::ArgNotFound { ref arg, suggestion: Some(ref suggestion), .. }
+                if arg == "primray" && suggestion == "primary"
+        ));
+
+        let Err(error) = evaluate_theme(&theme(r#"label = "${a""#)) else {
+            panic!("expected a missing closing bracket");
+        };
+        assert!(error.to_string().contains("Missing closing }"));
+    }
+}